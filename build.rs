@@ -0,0 +1,47 @@
+use std::path::{Path, PathBuf};
+
+/// With the `embedded-assets` feature, bakes every top-level PNG in `icons/` and WAV in
+/// `sounds/` into `$OUT_DIR/embedded_assets.rs` as `EMBEDDED_ICONS` and `EMBEDDED_SOUNDS`.
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    if std::env::var_os("CARGO_FEATURE_EMBEDDED_ASSETS").is_none() {
+        return;
+    }
+
+    let root = PathBuf::from(std::env::var_os("CARGO_MANIFEST_DIR").expect("CARGO_MANIFEST_DIR"));
+    let out = PathBuf::from(std::env::var_os("OUT_DIR").expect("OUT_DIR"));
+    let mut code = String::new();
+    for (name, folder, extension) in [
+        ("EMBEDDED_ICONS", "icons", "png"),
+        ("EMBEDDED_SOUNDS", "sounds", "wav"),
+    ] {
+        let dir = root.join(folder);
+        println!("cargo:rerun-if-changed={}", dir.display());
+        code.push_str(&format!("const {name}: &[(&str, &[u8])] = &[\n"));
+        for (id, path) in embedded_files(&dir, extension) {
+            code.push_str(&format!(
+                "    ({id:?}, include_bytes!({:?})),\n",
+                path.display().to_string()
+            ));
+        }
+        code.push_str("];\n");
+    }
+    std::fs::write(out.join("embedded_assets.rs"), code).expect("write embedded_assets.rs");
+}
+
+fn embedded_files(dir: &Path, extension: &str) -> Vec<(String, PathBuf)> {
+    let mut files: Vec<(String, PathBuf)> = std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .filter(|path| path.extension().and_then(|ext| ext.to_str()) == Some(extension))
+        .filter_map(|path| {
+            let id = path.file_stem()?.to_str()?.to_string();
+            Some((id, path))
+        })
+        .collect();
+    files.sort();
+    files
+}
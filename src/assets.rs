@@ -1,92 +1,711 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Once;
+use std::time::UNIX_EPOCH;
 
-pub fn list_icon_ids() -> Vec<String> {
-    let mut ids = Vec::new();
+use anyhow::{Context, Result};
+use serde::Serialize;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::config::Config;
+
+pub const WINDOWS_SOUND_IDS: &[&str] = &[
+    "default",
+    "im",
+    "mail",
+    "reminder",
+    "sms",
+    "alarm",
+    "incoming_call",
+];
+
+/// Icons written next to the exe when a first run finds no icons anywhere, so
+/// notifications work before any assets are installed.
+const DEFAULT_ICONS: &[(&str, &[u8])] = &[
+    ("error", include_bytes!("../icons/error.png")),
+    ("happy", include_bytes!("../icons/happy.png")),
+    ("proud", include_bytes!("../icons/proud.png")),
+    ("thinking", include_bytes!("../icons/thinking.png")),
+    ("warning", include_bytes!("../icons/warning.png")),
+];
+
+// With `embedded-assets`, build.rs bakes all of `icons/` and `sounds/` in as
+// `EMBEDDED_ICONS` and `EMBEDDED_SOUNDS`. They are listed straight from memory and
+// only written out, to a temp-dir cache, when a toast needs a file path.
+#[cfg(feature = "embedded-assets")]
+include!(concat!(env!("OUT_DIR"), "/embedded_assets.rs"));
+
+#[cfg(not(feature = "embedded-assets"))]
+const EMBEDDED_ICONS: &[(&str, &[u8])] = &[];
+#[cfg(not(feature = "embedded-assets"))]
+const EMBEDDED_SOUNDS: &[(&str, &[u8])] = &[];
+
+static ASSET_BOOTSTRAP: Once = Once::new();
+
+/// An asset id plus file metadata, for `list_assets` with `detailed: true`.
+#[derive(Debug, Clone, Serialize)]
+pub struct AssetInfo {
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    /// Last modified time, in seconds since the Unix epoch.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub modified: Option<u64>,
+}
+
+pub fn list_icon_ids(config: &Config) -> Vec<String> {
+    list_icon_details(config).into_iter().map(|asset| asset.id).collect()
+}
+
+pub fn list_sound_ids(config: &Config) -> Vec<String> {
+    list_sound_details(config).into_iter().map(|asset| asset.id).collect()
+}
+
+/// Icons in `icons/`, limited to the config's `allowed_icons`.
+pub fn list_icon_details(config: &Config) -> Vec<AssetInfo> {
+    let mut assets = scan_assets("icons", "png", config);
+    assets.retain(|asset| config.allows_icon(&asset.id));
+    assets
+}
+
+/// Sounds in `sounds/` (or the Windows system sounds if it is empty),
+/// limited to the config's `allowed_sounds`.
+pub fn list_sound_details(config: &Config) -> Vec<AssetInfo> {
+    let mut assets = scan_assets("sounds", "wav", config);
+    if assets.is_empty() {
+        assets = WINDOWS_SOUND_IDS
+            .iter()
+            .map(|id| AssetInfo {
+                id: id.to_string(),
+                size: None,
+                modified: None,
+            })
+            .collect();
+    }
+    assets.retain(|asset| config.allows_sound(&asset.id));
+    assets
+}
+
+/// Each listed sound id with the path of the file it resolves to, relative to
+/// `sounds/`, without the extension and with `/` separators (e.g. `chimes/soft`).
+/// Built-in and system sounds have no folder, so their path is just the id.
+pub fn list_sound_paths(config: &Config) -> Vec<(String, String)> {
+    let mut paths = std::collections::HashMap::new();
+    for dir in asset_dirs("sounds") {
+        for path in asset_files(&dir, "wav", &config.excluded_dirs) {
+            let Some(stem) = path.file_stem().and_then(|s| s.to_str()) else {
+                continue;
+            };
+            let relative = path.strip_prefix(&dir).unwrap_or(&path).with_extension("");
+            let relative: Vec<String> = relative
+                .iter()
+                .map(|part| nfc(&part.to_string_lossy()))
+                .collect();
+            // The first file wins, matching the order ids are resolved in.
+            paths.entry(nfc(stem)).or_insert_with(|| relative.join("/"));
+        }
+    }
+    list_sound_ids(config)
+        .into_iter()
+        .map(|id| {
+            let path = paths.remove(&id).unwrap_or_else(|| id.clone());
+            (id, path)
+        })
+        .collect()
+}
+
+/// Where an asset folder is looked up, in resolution order: next to the exe, then
+/// the source checkout.
+pub fn asset_dirs(folder: &str) -> Vec<PathBuf> {
     let mut candidates = Vec::new();
+    if let Ok(exe) = std::env::current_exe()
+        && let Some(dir) = exe.parent()
+    {
+        candidates.push(dir.join(folder));
+    }
+    candidates.push(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(folder));
+    candidates
+}
 
-    if let Ok(exe) = std::env::current_exe() {
-        if let Some(dir) = exe.parent() {
-            candidates.push(dir.join("icons"));
+/// Only reads the disk: listing ids (for `tools/list`, completions or `list_assets`)
+/// never creates the default assets, which `notify` does on first use.
+fn scan_assets(folder: &str, extension: &str, config: &Config) -> Vec<AssetInfo> {
+    let mut assets = scan_dirs(&asset_dirs(folder), extension, &config.excluded_dirs);
+    add_embedded(&mut assets, embedded_assets(folder));
+
+    // Stable sort keeps the exe-dir copy ahead of the manifest-dir one, and any file on
+    // disk ahead of a built-in asset, matching resolution order.
+    assets.sort_by(|a, b| a.id.cmp(&b.id));
+    assets.dedup_by(|a, b| a.id == b.id);
+    assets
+}
+
+/// Every asset file under `dirs`, in order, with its size and modified time.
+fn scan_dirs(dirs: &[PathBuf], extension: &str, excluded: &[String]) -> Vec<AssetInfo> {
+    let mut assets = Vec::new();
+    for dir in dirs {
+        for path in asset_files(dir, extension, excluded) {
+            if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
+                let metadata = std::fs::metadata(&path).ok();
+                assets.push(AssetInfo {
+                    id: nfc(stem),
+                    size: metadata.as_ref().map(|meta| meta.len()),
+                    modified: metadata
+                        .and_then(|meta| meta.modified().ok())
+                        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                        .map(|age| age.as_secs()),
+                });
+            }
         }
     }
-    candidates.push(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("icons"));
+    assets
+}
 
-    for dir in candidates {
-        if let Ok(entries) = std::fs::read_dir(dir) {
-            for entry in entries.flatten() {
-                let path = entry.path();
-                if path.extension().and_then(|ext| ext.to_str()) != Some("png") {
-                    continue;
-                }
-                if path
-                    .parent()
-                    .and_then(|p| p.file_name())
-                    .and_then(|name| name.to_str())
-                    .map(|name| name.eq_ignore_ascii_case("backup"))
-                    .unwrap_or(false)
-                {
-                    continue;
-                }
-                if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
-                    ids.push(stem.to_string());
+fn embedded_assets(folder: &str) -> &'static [(&'static str, &'static [u8])] {
+    match folder {
+        "icons" => EMBEDDED_ICONS,
+        "sounds" => EMBEDDED_SOUNDS,
+        _ => &[],
+    }
+}
+
+/// Appends the built-in assets after the ones found on disk.
+fn add_embedded(assets: &mut Vec<AssetInfo>, embedded: &[(&str, &[u8])]) {
+    assets.extend(embedded.iter().map(|(id, data)| AssetInfo {
+        id: nfc(id),
+        size: Some(data.len() as u64),
+        modified: None,
+    }));
+}
+
+/// Where built-in assets are written when a toast needs a real file. The exe's own
+/// folder may be read-only, so this lives in the temp dir, per version.
+fn embedded_cache_dir(folder: &str) -> PathBuf {
+    std::env::temp_dir()
+        .join(format!("toastmcp-{}", env!("CARGO_PKG_VERSION")))
+        .join(folder)
+}
+
+/// The on-disk copy of the built-in asset `file_name`, written to the embedded cache
+/// on first use unless `read_only`. `None` if this build has no such asset.
+pub fn embedded_asset_path(
+    folder: &str,
+    file_name: &str,
+    read_only: bool,
+) -> Option<Result<PathBuf>> {
+    materialize(
+        &embedded_cache_dir(folder),
+        file_name,
+        embedded_assets(folder),
+        read_only,
+    )
+}
+
+fn materialize(
+    dir: &Path,
+    file_name: &str,
+    embedded: &[(&str, &[u8])],
+    read_only: bool,
+) -> Option<Result<PathBuf>> {
+    let wanted = nfc(file_name);
+    let extension = Path::new(file_name).extension()?.to_str()?;
+    let (id, data) = embedded
+        .iter()
+        .find(|(id, _)| nfc(&format!("{id}.{extension}")) == wanted)?;
+    let path = dir.join(format!("{id}.{extension}"));
+    let current = std::fs::metadata(&path).is_ok_and(|meta| meta.len() == data.len() as u64);
+    if current || read_only {
+        return Some(Ok(path));
+    }
+    let written = std::fs::create_dir_all(dir)
+        .with_context(|| format!("Failed to create {}", dir.display()))
+        .and_then(|()| write_atomic(&path, data));
+    Some(written.map(|()| path))
+}
+
+/// Asset ids are compared in NFC. Filenames can be stored decomposed (NFD), e.g.
+/// by macOS, so `café` typed by an agent wouldn't otherwise match `café.png` on disk.
+pub fn nfc(text: &str) -> String {
+    text.nfc().collect()
+}
+
+/// Subfolders of `icons/` and `sounds/` are scanned this deep. An id is the bare file
+/// stem wherever the file sits, so folders only organize assets.
+const MAX_ASSET_DEPTH: usize = 8;
+
+/// Every `.{extension}` file under `root` at any depth, skipping excluded subfolders.
+/// Shallower files come first, so a top-level file wins over a nested one with the same id.
+pub fn asset_files(root: &Path, extension: &str, excluded: &[String]) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut pending = vec![(root.to_path_buf(), 0)];
+    while let Some((dir, depth)) = pending.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                let relative = path.strip_prefix(root).unwrap_or(&path);
+                if depth < MAX_ASSET_DEPTH && !is_excluded_dir(relative, excluded) {
+                    pending.push((path, depth + 1));
                 }
+            } else if path.extension().and_then(|ext| ext.to_str()) == Some(extension) {
+                files.push(path);
             }
         }
     }
-
-    ids.sort();
-    ids.dedup();
-    ids
+    files.sort_by_cached_key(|path| (path.components().count(), path.clone()));
+    files
 }
 
-pub fn list_sound_ids() -> Vec<String> {
-    const WINDOWS_SOUND_IDS: &[&str] = &[
-        "default",
-        "im",
-        "mail",
-        "reminder",
-        "sms",
-        "alarm",
-        "incoming_call",
-    ];
+/// The file under `dir` named `file_name`, compared in NFC, preferring the top level.
+pub fn find_asset_file(dir: &Path, file_name: &str, excluded: &[String]) -> Option<PathBuf> {
+    let candidate = dir.join(file_name);
+    if candidate.is_file() {
+        return Some(candidate);
+    }
+    let extension = Path::new(file_name).extension()?.to_str()?;
+    let wanted = nfc(file_name);
+    asset_files(dir, extension, excluded)
+        .into_iter()
+        .find(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| nfc(name) == wanted)
+        })
+}
 
-    let mut ids = Vec::new();
-    let mut candidates = Vec::new();
+/// Writes `data` to a temp file beside `path` and renames it into place, so readers
+/// (and concurrent writers of the same file) never see a partly written file.
+pub fn write_atomic(path: &Path, data: &[u8]) -> Result<()> {
+    static NEXT_TEMP: AtomicUsize = AtomicUsize::new(0);
 
-    if let Ok(exe) = std::env::current_exe() {
-        if let Some(dir) = exe.parent() {
-            candidates.push(dir.join("sounds"));
+    let dir = path
+        .parent()
+        .context("Cache path has no parent directory")?;
+    let name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .context("Cache path has no file name")?;
+    let temp = dir.join(format!(
+        ".{name}.{}.{}.tmp",
+        std::process::id(),
+        NEXT_TEMP.fetch_add(1, Ordering::Relaxed)
+    ));
+    std::fs::write(&temp, data).with_context(|| format!("Failed to write {}", temp.display()))?;
+    match std::fs::rename(&temp, path) {
+        Ok(()) => Ok(()),
+        Err(err) => {
+            let _ = std::fs::remove_file(&temp);
+            // Windows refuses to replace a file that is open for playback; the copy
+            // already there is complete, so it can stay.
+            if path.is_file() {
+                Ok(())
+            } else {
+                Err(err).with_context(|| format!("Failed to write {}", path.display()))
+            }
         }
     }
-    candidates.push(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("sounds"));
+}
 
-    for dir in candidates {
-        if let Ok(entries) = std::fs::read_dir(dir) {
-            for entry in entries.flatten() {
-                let path = entry.path();
-                if path.extension().and_then(|ext| ext.to_str()) != Some("wav") {
-                    continue;
-                }
-                if path
+/// Where `bootstrap_default_icons` would put `file_name`, if it hasn't run yet and
+/// would write it. Lets a dry run report the icon without creating anything.
+pub fn planned_default_icon(file_name: &str, excluded: &[String]) -> Option<PathBuf> {
+    let id = file_name.strip_suffix(".png")?;
+    if !EMBEDDED_ICONS.is_empty() || !DEFAULT_ICONS.iter().any(|(name, _)| *name == id) {
+        return None;
+    }
+    if asset_dirs("icons")
+        .iter()
+        .any(|dir| !asset_files(dir, "png", excluded).is_empty())
+    {
+        return None;
+    }
+    let exe = std::env::current_exe().ok()?;
+    Some(exe.parent()?.join("icons").join(file_name))
+}
+
+/// Creates `icons/` next to the exe with the default icons if no icon folder holds
+/// any PNG outside the `excluded` subfolders yet. Only the first call per process
+/// does any work, and a build with embedded icons never needs it.
+pub fn bootstrap_default_icons(excluded: &[String]) {
+    if !EMBEDDED_ICONS.is_empty() {
+        return;
+    }
+    ASSET_BOOTSTRAP.call_once(|| {
+        let written = std::env::current_exe()
+            .context("Failed to resolve exe path")
+            .and_then(|exe| {
+                let dir = exe
                     .parent()
-                    .and_then(|p| p.file_name())
-                    .and_then(|name| name.to_str())
-                    .map(|name| name.eq_ignore_ascii_case("backup"))
-                    .unwrap_or(false)
-                {
-                    continue;
-                }
-                if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
-                    ids.push(stem.to_string());
-                }
+                    .context("Failed to resolve exe directory")?
+                    .join("icons");
+                write_default_assets(&asset_dirs("icons"), &dir, "png", DEFAULT_ICONS, excluded)
+            });
+        if let Err(err) = written {
+            eprintln!("toastmcp: failed to create default icons: {err:#}");
+        }
+    });
+}
+
+/// Writes `assets` into `dir` unless one of `dirs` already has a file of this type
+/// that the listings would see, so on-disk assets always win over the built-in ones.
+fn write_default_assets(
+    dirs: &[PathBuf],
+    dir: &Path,
+    extension: &str,
+    assets: &[(&str, &[u8])],
+    excluded: &[String],
+) -> Result<()> {
+    let has_assets = dirs
+        .iter()
+        .any(|dir| !asset_files(dir, extension, excluded).is_empty());
+    if has_assets || assets.is_empty() {
+        return Ok(());
+    }
+
+    std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    for (id, data) in assets {
+        let path = dir.join(format!("{id}.{extension}"));
+        std::fs::write(&path, data)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+    }
+    eprintln!("toastmcp: created default assets in {}", dir.display());
+    Ok(())
+}
+
+/// A `cache/` folder inside `sounds/` or `icons/` (say, one a user created by hand)
+/// holds copies such as `done_vol70.wav` that would show up as spurious ids, so it
+/// is skipped at any depth whatever `excluded_dirs` says.
+const ALWAYS_EXCLUDED_DIRS: &[&str] = &["cache"];
+
+/// Whether `relative`, a folder path below `icons/` or `sounds/`, is or sits inside
+/// an excluded folder. Only the part below the asset folder counts, so an install
+/// under `D:\backup\toastmcp` still finds its own icons.
+fn is_excluded_dir(relative: &Path, excluded: &[String]) -> bool {
+    relative.components().any(|component| {
+        let name = component.as_os_str().to_string_lossy();
+        ALWAYS_EXCLUDED_DIRS
+            .iter()
+            .copied()
+            .chain(excluded.iter().map(String::as_str))
+            .any(|excluded| name.eq_ignore_ascii_case(excluded))
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// A WAV in the `sounds/` folder next to the test exe, removed again on drop. The
+    /// folder itself is left in place, since other tests may be using it.
+    pub(crate) struct SoundFixture(pub(crate) PathBuf);
+
+    impl SoundFixture {
+        pub(crate) fn new(id: &str) -> Self {
+            let exe = std::env::current_exe().unwrap();
+            let path = exe
+                .parent()
+                .unwrap()
+                .join("sounds")
+                .join(format!("{id}.wav"));
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, crate::wav::tests::wav_bytes(4, &[0x10, 0, 0x20, 0])).unwrap();
+            Self(path)
+        }
+    }
+
+    impl Drop for SoundFixture {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+            if let Some(dir) = self.0.parent()
+                && !dir.ends_with("sounds")
+            {
+                let _ = std::fs::remove_dir(dir);
             }
         }
     }
 
-    ids.sort();
-    ids.dedup();
-    if ids.is_empty() {
-        return WINDOWS_SOUND_IDS.iter().map(|s| s.to_string()).collect();
+    /// A fresh, empty directory under the system temp dir.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("toastmcp-test-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn touch(root: &Path, relative: &str) {
+        let path = root.join(relative);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, b"").unwrap();
+    }
+
+    fn relative_files(root: &Path, extension: &str, excluded: &[&str]) -> Vec<String> {
+        let excluded: Vec<String> = excluded.iter().map(|name| name.to_string()).collect();
+        asset_files(root, extension, &excluded)
+            .iter()
+            .map(|path| {
+                let relative = path.strip_prefix(root).unwrap();
+                relative.to_string_lossy().replace('\\', "/")
+            })
+            .collect()
+    }
+
+    #[test]
+    fn asset_files_skips_excluded_folders_at_any_depth() {
+        let root = temp_dir("excluded").join("icons");
+        for file in [
+            "done.png",
+            "backup/old.png",
+            "Archive/older.png",
+            "packs/party.png",
+            "packs/WIP/draft.png",
+            "packs/backups/kept.png",
+            "packs/deep/er/nested.png",
+            "notes.txt",
+        ] {
+            touch(&root, file);
+        }
+        assert_eq!(
+            relative_files(&root, "png", &["backup", "archive", "wip"]),
+            [
+                "done.png",
+                "packs/party.png",
+                "packs/backups/kept.png",
+                "packs/deep/er/nested.png"
+            ]
+        );
+        assert_eq!(relative_files(&root, "png", &[]).len(), 7);
+        std::fs::remove_dir_all(root.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn exclusion_only_applies_below_the_asset_folder() {
+        let root = temp_dir("outer").join("backup").join("icons");
+        touch(&root, "done.png");
+        touch(&root, "backup/old.png");
+        assert_eq!(relative_files(&root, "png", &["backup"]), ["done.png"]);
+        std::fs::remove_dir_all(root.parent().unwrap().parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn cache_folders_are_always_skipped() {
+        let root = temp_dir("cache").join("sounds");
+        touch(&root, "foo.wav");
+        touch(&root, "cache/foo_vol70.wav");
+        touch(&root, "packs/Cache/bar_vol50.wav");
+        assert_eq!(relative_files(&root, "wav", &[]), ["foo.wav"]);
+        assert_eq!(find_asset_file(&root, "foo_vol70.wav", &[]), None);
+        std::fs::remove_dir_all(root.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn excluded_dir_matches_any_component() {
+        let excluded = vec!["backup".to_string(), "wip".to_string()];
+        assert!(is_excluded_dir(Path::new("Backup"), &excluded));
+        assert!(is_excluded_dir(Path::new("packs/wip"), &excluded));
+        assert!(is_excluded_dir(Path::new("wip/packs"), &excluded));
+        assert!(!is_excluded_dir(Path::new("packs/backups"), &excluded));
+        assert!(!is_excluded_dir(Path::new(""), &excluded));
+    }
+
+    #[test]
+    fn find_asset_file_prefers_the_top_level() {
+        let root = temp_dir("find").join("sounds");
+        touch(&root, "chimes/done.wav");
+        touch(&root, "backup/old.wav");
+        let excluded = vec!["backup".to_string()];
+        assert_eq!(
+            find_asset_file(&root, "done.wav", &excluded),
+            Some(root.join("chimes").join("done.wav"))
+        );
+        touch(&root, "done.wav");
+        assert_eq!(
+            find_asset_file(&root, "done.wav", &excluded),
+            Some(root.join("done.wav"))
+        );
+        assert_eq!(find_asset_file(&root, "old.wav", &excluded), None);
+        std::fs::remove_dir_all(root.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn default_assets_are_written_only_when_none_exist() {
+        let root = temp_dir("bootstrap");
+        let exe_icons = root.join("exe").join("icons");
+        let dirs = vec![exe_icons.clone(), root.join("checkout").join("icons")];
+        let assets: &[(&str, &[u8])] = &[("happy", b"png-a"), ("error", b"png-b")];
+
+        write_default_assets(&dirs, &exe_icons, "png", assets, &[]).unwrap();
+        assert_eq!(
+            std::fs::read(exe_icons.join("happy.png")).unwrap(),
+            b"png-a"
+        );
+        assert_eq!(
+            std::fs::read(exe_icons.join("error.png")).unwrap(),
+            b"png-b"
+        );
+
+        std::fs::remove_dir_all(&exe_icons).unwrap();
+        touch(&dirs[1], "mine/custom.png");
+        write_default_assets(&dirs, &exe_icons, "png", assets, &[]).unwrap();
+        assert!(!exe_icons.exists());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn icons_in_excluded_folders_still_get_default_icons() {
+        let root = temp_dir("bootstrap-excluded");
+        let exe_icons = root.join("exe").join("icons");
+        let dirs = vec![exe_icons.clone()];
+        let assets: &[(&str, &[u8])] = &[("happy", b"png-a")];
+        touch(&exe_icons, "backup/old.png");
+        let excluded = vec!["backup".to_string()];
+
+        assert!(scan_dirs(&dirs, "png", &excluded).is_empty());
+        write_default_assets(&dirs, &exe_icons, "png", assets, &excluded).unwrap();
+        assert_eq!(
+            std::fs::read(exe_icons.join("happy.png")).unwrap(),
+            b"png-a"
+        );
+        assert_eq!(scan_dirs(&dirs, "png", &excluded).len(), 1);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    fn info(id: &str) -> AssetInfo {
+        AssetInfo {
+            id: id.to_string(),
+            size: Some(1),
+            modified: Some(1),
+        }
+    }
+
+    #[test]
+    fn disk_assets_win_over_embedded_ones_per_id() {
+        let mut assets = vec![info("happy")];
+        add_embedded(
+            &mut assets,
+            &[("error", b"built-in"), ("happy", b"built-in")],
+        );
+        assets.sort_by(|a, b| a.id.cmp(&b.id));
+        assets.dedup_by(|a, b| a.id == b.id);
+        let listed: Vec<_> = assets
+            .iter()
+            .map(|asset| (asset.id.as_str(), asset.modified))
+            .collect();
+        assert_eq!(listed, [("error", None), ("happy", Some(1))]);
+    }
+
+    #[test]
+    fn embedded_assets_are_written_on_first_use() {
+        let dir = temp_dir("materialize").join("icons");
+        let embedded: &[(&str, &[u8])] = &[("happy", b"png-bytes")];
+        assert!(materialize(&dir, "missing.png", embedded, false).is_none());
+        let planned = materialize(&dir, "happy.png", embedded, true)
+            .unwrap()
+            .unwrap();
+        assert_eq!(planned, dir.join("happy.png"));
+        assert!(!dir.exists());
+
+        let path = materialize(&dir, "happy.png", embedded, false)
+            .unwrap()
+            .unwrap();
+        assert_eq!(path, dir.join("happy.png"));
+        assert_eq!(std::fs::read(&path).unwrap(), b"png-bytes");
+        std::fs::write(&path, b"stale").unwrap();
+        materialize(&dir, "happy.png", embedded, false)
+            .unwrap()
+            .unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"png-bytes");
+        std::fs::remove_dir_all(dir.parent().unwrap()).unwrap();
+    }
+
+    #[cfg(feature = "embedded-assets")]
+    #[test]
+    fn embedded_build_lists_and_resolves_without_files_next_to_the_exe() {
+        let config = Config::default();
+        let icons = list_icon_ids(&config);
+        for (id, data) in EMBEDDED_ICONS {
+            assert!(icons.contains(&nfc(id)), "{id}");
+            let path = embedded_asset_path("icons", &format!("{id}.png"), false)
+                .unwrap()
+                .unwrap();
+            assert_eq!(std::fs::read(path).unwrap(), *data);
+        }
+        assert!(!EMBEDDED_ICONS.is_empty());
+    }
+
+    #[test]
+    fn decomposed_file_names_match_composed_ids() {
+        let root = temp_dir("nfc");
+        let decomposed = "cafe\u{301}";
+        touch(&root, &format!("{decomposed}.png"));
+        touch(&root, "menu/the\u{301}.png");
+
+        let ids: Vec<String> = scan_dirs(std::slice::from_ref(&root), "png", &[])
+            .into_iter()
+            .map(|asset| asset.id)
+            .collect();
+        assert_eq!(ids, ["caf\u{e9}", "th\u{e9}"]);
+
+        let found = find_asset_file(&root, "caf\u{e9}.png", &[]).unwrap();
+        assert_eq!(found, root.join(format!("{decomposed}.png")));
+        let found = find_asset_file(&root, "th\u{e9}.png", &[]).unwrap();
+        assert_eq!(found, root.join("menu").join("the\u{301}.png"));
+        assert!(find_asset_file(&root, "cafe.png", &[]).is_none());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn scan_reports_size_and_modified_time_per_file() {
+        let root = temp_dir("details");
+        let first = root.join("first");
+        let second = root.join("second");
+        std::fs::create_dir_all(&first).unwrap();
+        std::fs::create_dir_all(second.join("team")).unwrap();
+        std::fs::write(first.join("chime.wav"), [0; 12]).unwrap();
+        std::fs::write(second.join("chime.wav"), [0; 99]).unwrap();
+        std::fs::write(second.join("team").join("bell.wav"), [0; 7]).unwrap();
+        let now = std::time::SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+
+        let assets = scan_dirs(&[first, second], "wav", &[]);
+        let listed: Vec<_> = assets
+            .iter()
+            .map(|asset| (asset.id.as_str(), asset.size))
+            .collect();
+        assert_eq!(
+            listed,
+            [("chime", Some(12)), ("chime", Some(99)), ("bell", Some(7))]
+        );
+        for asset in &assets {
+            let modified = asset.modified.unwrap();
+            assert!(now.abs_diff(modified) < 60, "{modified} vs {now}");
+        }
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn write_atomic_replaces_without_leaving_temp_files() {
+        let dir = temp_dir("write-atomic");
+        let path = dir.join("done_vol70.wav");
+        write_atomic(&path, b"first").unwrap();
+        write_atomic(&path, b"second").unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"second");
+        let names: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .flatten()
+            .map(|entry| entry.file_name())
+            .collect();
+        assert_eq!(names, vec![std::ffi::OsString::from("done_vol70.wav")]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn write_atomic_fails_when_the_directory_is_missing() {
+        let dir = temp_dir("write-atomic-missing");
+        assert!(write_atomic(&dir.join("missing").join("a.wav"), b"data").is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
     }
-    ids
 }
@@ -0,0 +1,426 @@
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::log::LogLevel;
+
+const CONFIG_ENV: &str = "TOASTMCP_CONFIG";
+const CONFIG_FILE_NAME: &str = "toastmcp.toml";
+/// Longer than this and a coalesced progress bar would visibly lag behind the work.
+const MAX_PROGRESS_DEBOUNCE_MS: u64 = 5_000;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Name users see as the notification source (the Start Menu shortcut name).
+    /// The internal app id stays `ToastMCP` so grouping and history keep working.
+    pub display_name: String,
+    /// Playback volume (0.0-1.0) applied to WAV sounds before playing.
+    pub volume: f64,
+    /// Where no audio backend exists, ring the terminal bell on stderr instead of failing.
+    pub terminal_bell: bool,
+    /// Pre-build the volume-adjusted WAV cache on a background thread at startup.
+    pub warmup_sounds: bool,
+    /// On shutdown, delete ephemeral files from `cache/` (everything but volume-scaled WAVs).
+    pub cleanup_on_exit: bool,
+    /// With `cleanup_on_exit`, also delete the reusable volume-scaled WAVs.
+    pub cleanup_sound_cache: bool,
+    /// Glob patterns (`*`, `?`) limiting which icon ids are listed and accepted.
+    /// Unset means every icon is allowed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allowed_icons: Option<Vec<String>>,
+    /// Same as `allowed_icons`, for sound ids (including Windows system sounds).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allowed_sounds: Option<Vec<String>>,
+    /// Glob patterns for icon ids to hide and reject. Deny wins over `allowed_icons`.
+    pub denied_icons: Vec<String>,
+    /// Same as `denied_icons`, for sound ids.
+    pub denied_sounds: Vec<String>,
+    /// Asset subfolder names the scanners skip at any depth, compared case-insensitively.
+    pub excluded_dirs: Vec<String>,
+    /// After the first notification succeeds, show a one-time welcome toast.
+    pub show_onboarding: bool,
+    /// How WAV sounds are delivered; see `AudioMode`.
+    pub audio_mode: AudioMode,
+    /// Play WAVs one after another instead of cutting off the one still playing.
+    pub queue_sounds: bool,
+    /// With `queue_sounds`, sounds arriving while this many are waiting are skipped.
+    pub max_queued_sounds: usize,
+    /// `update_progress` calls for one tag closer together than this are coalesced, and
+    /// only the latest is pushed once the interval ends. 0 pushes every update.
+    pub progress_debounce_ms: u64,
+    /// Append JSON log lines (requests, errors, framing traces) to this file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub log_file: Option<PathBuf>,
+    /// Least severe level written to `log_file`.
+    pub log_level: LogLevel,
+    /// Never read from the file: set for dry runs, so resolving assets and preparing
+    /// sounds report the paths they would use without writing anything.
+    #[serde(skip)]
+    pub read_only: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AudioMode {
+    /// Play the WAV and keep the toast itself silent.
+    #[default]
+    File,
+    /// Also give the toast a system `<audio>` sound, in case the WAV is muted.
+    Both,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            display_name: "ToastMCP".to_string(),
+            volume: 0.7,
+            terminal_bell: false,
+            warmup_sounds: false,
+            cleanup_on_exit: false,
+            cleanup_sound_cache: false,
+            allowed_icons: None,
+            allowed_sounds: None,
+            denied_icons: Vec::new(),
+            denied_sounds: Vec::new(),
+            excluded_dirs: vec!["backup".to_string()],
+            show_onboarding: false,
+            audio_mode: AudioMode::File,
+            queue_sounds: false,
+            max_queued_sounds: 4,
+            progress_debounce_ms: 200,
+            log_file: None,
+            log_level: LogLevel::Info,
+            read_only: false,
+        }
+    }
+}
+
+impl Config {
+    fn validate(&self) -> Result<()> {
+        if self.display_name.trim().is_empty()
+            || self
+                .display_name
+                .contains(['\\', '/', ':', '*', '?', '"', '<', '>', '|'])
+        {
+            return Err(anyhow!(
+                "display_name must be a non-empty valid file name (got {:?})",
+                self.display_name
+            ));
+        }
+        if !(0.0..=1.0).contains(&self.volume) {
+            return Err(anyhow!(
+                "volume must be between 0.0 and 1.0 (got {})",
+                self.volume
+            ));
+        }
+        if self.progress_debounce_ms > MAX_PROGRESS_DEBOUNCE_MS {
+            return Err(anyhow!(
+                "progress_debounce_ms must be at most {MAX_PROGRESS_DEBOUNCE_MS} (got {})",
+                self.progress_debounce_ms
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn allows_icon(&self, id: &str) -> bool {
+        allowed_by(self.allowed_icons.as_deref(), &self.denied_icons, id)
+    }
+
+    pub fn allows_sound(&self, id: &str) -> bool {
+        allowed_by(self.allowed_sounds.as_deref(), &self.denied_sounds, id)
+    }
+}
+
+/// An id is usable if no deny pattern matches it and, when an allowlist is set,
+/// some allow pattern does.
+fn allowed_by(allowed: Option<&[String]>, denied: &[String], id: &str) -> bool {
+    let matches_any = |patterns: &[String]| patterns.iter().any(|pattern| glob_match(pattern, id));
+    !matches_any(denied) && allowed.is_none_or(matches_any)
+}
+
+/// Matches `*` (any run) and `?` (one char), ignoring ASCII case like Windows file names.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().map(|c| c.to_ascii_lowercase()).collect();
+    let text: Vec<char> = text.chars().map(|c| c.to_ascii_lowercase()).collect();
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            t = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Shared, swappable config. Readers take an `Arc` snapshot, so a reload never
+/// changes the settings of a notification that is already being shown.
+/// The file it was loaded from is kept alongside, so both always change together.
+#[derive(Debug)]
+pub struct ConfigHandle(RwLock<(Arc<Config>, Option<PathBuf>)>);
+
+impl ConfigHandle {
+    pub fn new(config: Config, path: Option<PathBuf>) -> Self {
+        Self(RwLock::new((Arc::new(config), path)))
+    }
+
+    pub fn current(&self) -> Arc<Config> {
+        self.0
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .0
+            .clone()
+    }
+
+    /// The file the current config was loaded from; `None` means built-in defaults.
+    pub fn path(&self) -> Option<PathBuf> {
+        self.0
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .1
+            .clone()
+    }
+
+    pub fn replace(&self, config: Config, path: Option<PathBuf>) -> Arc<Config> {
+        let config = Arc::new(config);
+        *self
+            .0
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = (config.clone(), path);
+        config
+    }
+}
+
+/// Loads the first config file found in `candidate_paths`, or defaults if there is none,
+/// along with the path it came from.
+pub fn load() -> Result<(Config, Option<PathBuf>)> {
+    let Some(path) = find() else {
+        return Ok((Config::default(), None));
+    };
+    eprintln!("toastmcp: using config {}", path.display());
+    let config = load_from(&path)?;
+    Ok((config, Some(path)))
+}
+
+/// The config file `load` would use, if any.
+pub fn find() -> Option<PathBuf> {
+    first_file(candidate_paths())
+}
+
+fn first_file(candidates: Vec<PathBuf>) -> Option<PathBuf> {
+    candidates.into_iter().find(|path| path.is_file())
+}
+
+/// Validates the config file and prints the effective values, for `--config-check`.
+pub fn check() -> Result<()> {
+    print!("{}", check_report(find().as_deref())?);
+    Ok(())
+}
+
+fn check_report(path: Option<&Path>) -> Result<String> {
+    let (header, config) = match path {
+        Some(path) => (format!("# config: {}", path.display()), load_from(path)?),
+        None => (
+            "# no config file found; using defaults".to_string(),
+            Config::default(),
+        ),
+    };
+    Ok(format!("{header}\n{}", toml::to_string(&config)?))
+}
+
+pub fn load_from(path: &Path) -> Result<Config> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read config {}", path.display()))?;
+    let config: Config = toml::from_str(&text)
+        .with_context(|| format!("Invalid config {}", path.display()))?;
+    config
+        .validate()
+        .with_context(|| format!("Invalid config {}", path.display()))?;
+    Ok(config)
+}
+
+/// Config search order: `TOASTMCP_CONFIG`, the working directory, the exe directory,
+/// then `%APPDATA%\ToastMCP\config.toml`.
+pub fn candidate_paths() -> Vec<PathBuf> {
+    let exe = std::env::current_exe().ok();
+    candidates_from(
+        std::env::var_os(CONFIG_ENV),
+        exe.as_deref().and_then(Path::parent),
+        std::env::var_os("APPDATA"),
+    )
+}
+
+fn candidates_from(
+    env_path: Option<OsString>,
+    exe_dir: Option<&Path>,
+    appdata: Option<OsString>,
+) -> Vec<PathBuf> {
+    let mut candidates = Vec::new();
+
+    if let Some(path) = env_path.filter(|path| !path.is_empty()) {
+        candidates.push(PathBuf::from(path));
+    }
+    candidates.push(PathBuf::from(CONFIG_FILE_NAME));
+    if let Some(dir) = exe_dir {
+        candidates.push(dir.join(CONFIG_FILE_NAME));
+    }
+    if let Some(appdata) = appdata {
+        candidates.push(PathBuf::from(appdata).join("ToastMCP").join("config.toml"));
+    }
+
+    candidates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("toastmcp-test-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write(path: &Path, text: &str) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, text).unwrap();
+    }
+
+    #[test]
+    fn candidates_follow_the_documented_order() {
+        let candidates = candidates_from(
+            Some(OsString::from("custom.toml")),
+            Some(Path::new("exe")),
+            Some(OsString::from("appdata")),
+        );
+        assert_eq!(
+            candidates,
+            [
+                PathBuf::from("custom.toml"),
+                PathBuf::from(CONFIG_FILE_NAME),
+                Path::new("exe").join(CONFIG_FILE_NAME),
+                Path::new("appdata").join("ToastMCP").join("config.toml"),
+            ]
+        );
+        let without_env = candidates_from(Some(OsString::new()), None, None);
+        assert_eq!(without_env, [PathBuf::from(CONFIG_FILE_NAME)]);
+    }
+
+    #[test]
+    fn the_first_existing_candidate_wins() {
+        let dir = temp_dir("config-precedence");
+        let env_file = dir.join("env.toml");
+        let exe_file = dir.join("exe").join(CONFIG_FILE_NAME);
+        let appdata_file = dir.join("appdata").join("ToastMCP").join("config.toml");
+        write(&exe_file, "volume = 0.2");
+        write(&appdata_file, "volume = 0.3");
+        let candidates = || {
+            candidates_from(
+                Some(env_file.clone().into_os_string()),
+                Some(&dir.join("exe")),
+                Some(dir.join("appdata").into_os_string()),
+            )
+        };
+
+        assert_eq!(first_file(candidates()), Some(exe_file.clone()));
+        write(&env_file, "volume = 0.1");
+        assert_eq!(first_file(candidates()), Some(env_file.clone()));
+        std::fs::remove_file(&env_file).unwrap();
+        std::fs::remove_file(&exe_file).unwrap();
+        assert_eq!(first_file(candidates()), Some(appdata_file.clone()));
+        assert_eq!(load_from(&appdata_file).unwrap().volume, 0.3);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn check_reports_the_file_and_rejects_invalid_values() {
+        let dir = temp_dir("config-check");
+        let path = dir.join(CONFIG_FILE_NAME);
+        write(&path, "display_name = \"My Agent\"\nvolume = 0.5");
+        let report = check_report(Some(&path)).unwrap();
+        assert!(report.starts_with(&format!("# config: {}\n", path.display())));
+        assert!(report.contains("display_name = \"My Agent\""));
+        assert!(report.contains("volume = 0.5"));
+
+        write(&path, "volume = 1.5");
+        let err = format!("{:#}", check_report(Some(&path)).unwrap_err());
+        assert!(err.contains("volume must be between 0.0 and 1.0"), "{err}");
+        write(&path, "progress_debounce_ms = 60000");
+        let err = format!("{:#}", check_report(Some(&path)).unwrap_err());
+        assert!(
+            err.contains("progress_debounce_ms must be at most 5000"),
+            "{err}"
+        );
+        write(&path, "unknown_key = true");
+        assert!(check_report(Some(&path)).is_err());
+
+        let defaults = check_report(None).unwrap();
+        assert!(defaults.starts_with("# no config file found; using defaults\n"));
+        assert!(defaults.contains("display_name = \"ToastMCP\""));
+        assert!(defaults.contains("progress_debounce_ms = 200"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn glob_match_handles_stars_and_question_marks() {
+        assert!(glob_match("*", "anything"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("happy", "happy"));
+        assert!(glob_match("HAPPY", "happy"));
+        assert!(glob_match("h?ppy", "happy"));
+        assert!(glob_match("team/*", "team/alert"));
+        assert!(glob_match("*-alert", "build-alert"));
+        assert!(glob_match("a*b*c", "axxbyyc"));
+        assert!(glob_match("a*b*c", "abcbc"));
+        assert!(!glob_match("happy", "unhappy"));
+        assert!(!glob_match("h?ppy", "hppy"));
+        assert!(!glob_match("team/*", "teams/alert"));
+        assert!(!glob_match("a*b*c", "axxbyy"));
+    }
+
+    #[test]
+    fn allowlists_limit_the_usable_ids() {
+        let config = Config::default();
+        assert!(config.allows_icon("anything"));
+        assert!(config.allows_sound("anything"));
+
+        let config = Config {
+            allowed_icons: Some(vec!["happy".to_string(), "team/*".to_string()]),
+            allowed_sounds: Some(Vec::new()),
+            ..Config::default()
+        };
+        assert!(config.allows_icon("happy"));
+        assert!(config.allows_icon("team/alert"));
+        assert!(!config.allows_icon("error"));
+        assert!(!config.allows_sound("default"));
+    }
+
+    #[test]
+    fn denylists_win_over_allowlists() {
+        let config = Config {
+            allowed_icons: Some(vec!["team/*".to_string()]),
+            denied_icons: vec!["team/draft-*".to_string()],
+            denied_sounds: vec!["experimental*".to_string()],
+            ..Config::default()
+        };
+        assert!(config.allows_icon("team/alert"));
+        assert!(!config.allows_icon("team/draft-alert"));
+        assert!(!config.allows_icon("happy"));
+        assert!(config.allows_sound("chime"));
+        assert!(!config.allows_sound("Experimental-Beep"));
+    }
+}
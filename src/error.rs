@@ -0,0 +1,45 @@
+use std::fmt;
+
+/// Notification failures callers may want to tell apart; everything else stays `anyhow`.
+#[derive(Debug, Clone)]
+pub enum NotifyError {
+    /// The sound file exists but isn't a playable RIFF/WAVE file.
+    AudioDecode { sound: String, reason: String },
+    /// Toasts can't work on this OS at all, as opposed to being turned off by the user.
+    PlatformUnsupported { reason: String },
+    /// The toast XML could be past the payload size Windows accepts.
+    ToastTooLarge { bytes: usize, limit: usize },
+}
+
+impl NotifyError {
+    /// Stable identifier for the `errorCode` field of structured tool results.
+    pub fn code(&self) -> &'static str {
+        match self {
+            NotifyError::AudioDecode { .. } => "audio_decode",
+            NotifyError::PlatformUnsupported { .. } => "platform_unsupported",
+            NotifyError::ToastTooLarge { .. } => "toast_too_large",
+        }
+    }
+}
+
+impl fmt::Display for NotifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NotifyError::AudioDecode { sound, reason } => {
+                write!(f, "Sound {sound} is not a playable WAV file: {reason}")
+            }
+            NotifyError::PlatformUnsupported { reason } => {
+                write!(f, "Toast notifications are not supported here: {reason}")
+            }
+            NotifyError::ToastTooLarge { bytes, limit } => {
+                write!(
+                    f,
+                    "Toast XML would be up to {bytes} bytes, over the {limit}-byte limit; \
+                     shorten the text"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for NotifyError {}
@@ -0,0 +1,99 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use serde::Serialize;
+
+use crate::config::Config;
+use crate::notify::{notify, NotifyInput, NotifyTimings};
+
+/// Finished jobs kept around for `notify_status`; the oldest are dropped first.
+const MAX_TRACKED_JOBS: usize = 1024;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum JobStatus {
+    Pending,
+    Succeeded {
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        warnings: Vec<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        sound: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        timings: Option<NotifyTimings>,
+    },
+    Failed { error: String },
+}
+
+struct Job {
+    id: u64,
+    input: NotifyInput,
+    config: Arc<Config>,
+}
+
+/// Runs `notify` calls on a background worker so the RPC loop can answer immediately.
+pub struct JobQueue {
+    sender: Sender<Job>,
+    statuses: Arc<Mutex<BTreeMap<u64, JobStatus>>>,
+    next_id: AtomicU64,
+}
+
+impl JobQueue {
+    pub fn spawn() -> Self {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let statuses = Arc::new(Mutex::new(BTreeMap::new()));
+        let worker_statuses = statuses.clone();
+        std::thread::spawn(move || {
+            for job in receiver {
+                let status = match notify(job.input, &job.config) {
+                    Ok(outcome) => JobStatus::Succeeded {
+                        warnings: outcome.warnings,
+                        sound: outcome.sound,
+                        timings: outcome.timings,
+                    },
+                    Err(err) => JobStatus::Failed {
+                        error: err.to_string(),
+                    },
+                };
+                lock(&worker_statuses).insert(job.id, status);
+            }
+        });
+
+        Self {
+            sender,
+            statuses,
+            next_id: AtomicU64::new(1),
+        }
+    }
+
+    pub fn submit(&self, input: NotifyInput, config: Arc<Config>) -> u64 {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        {
+            let mut statuses = lock(&self.statuses);
+            statuses.insert(id, JobStatus::Pending);
+            while statuses.len() > MAX_TRACKED_JOBS {
+                statuses.pop_first();
+            }
+        }
+        if self.sender.send(Job { id, input, config }).is_err() {
+            lock(&self.statuses).insert(
+                id,
+                JobStatus::Failed {
+                    error: "Notification worker is not running".to_string(),
+                },
+            );
+        }
+        id
+    }
+
+    pub fn status(&self, id: u64) -> Option<JobStatus> {
+        lock(&self.statuses).get(&id).cloned()
+    }
+}
+
+fn lock(statuses: &Mutex<BTreeMap<u64, JobStatus>>) -> MutexGuard<'_, BTreeMap<u64, JobStatus>> {
+    statuses
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
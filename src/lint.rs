@@ -0,0 +1,273 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::assets::{asset_dirs, asset_files, nfc};
+use crate::config::Config;
+use crate::notify::AssetKind;
+use crate::{png, wav};
+
+/// Windows drops toast images larger than this in either dimension...
+const MAX_ICON_DIMENSION: u32 = 1024;
+/// ...or bigger than this on disk.
+const MAX_ICON_BYTES: u64 = 3 * 1024 * 1024;
+
+/// Extensions that look like an asset of each kind, for spotting id collisions.
+const ICON_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "gif", "bmp", "ico", "svg", "webp"];
+const SOUND_EXTENSIONS: &[&str] = &["wav", "mp3", "ogg", "flac", "m4a", "wma"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LintIssue {
+    EmptyFile,
+    Unreadable,
+    InvalidPng,
+    NoAlpha,
+    OversizedIcon,
+    InvalidWav,
+    /// Plays, but can't be volume-scaled.
+    NotPcm16,
+    /// Several files share an id, e.g. `done.png` and `done.jpg`.
+    NameCollision,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LintProblem {
+    pub kind: AssetKind,
+    pub id: String,
+    pub path: PathBuf,
+    pub issue: LintIssue,
+    pub message: String,
+}
+
+/// Result of `lint_assets`: how many assets were checked and what's wrong with them.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LintReport {
+    pub icons_checked: usize,
+    pub sounds_checked: usize,
+    pub problems: Vec<LintProblem>,
+}
+
+/// Audits every file under the `icons/` and `sounds/` folders, including ones the
+/// config's allow/deny lists hide, since asset packs are shared between setups.
+/// Folders in `excluded_dirs` are skipped, as they are when listing assets.
+pub fn lint_assets(config: &Config) -> LintReport {
+    let mut report = LintReport::default();
+    for kind in [AssetKind::Icon, AssetKind::Sound] {
+        for dir in asset_dirs(kind.folder()) {
+            lint_dir(kind, &dir, &config.excluded_dirs, &mut report);
+        }
+    }
+    report
+}
+
+fn lint_dir(kind: AssetKind, dir: &Path, excluded: &[String], report: &mut LintReport) {
+    let lookalikes = match kind {
+        AssetKind::Icon => ICON_EXTENSIONS,
+        AssetKind::Sound => SOUND_EXTENSIONS,
+    };
+
+    // Ids are file stems wherever the file sits, so `a/done.wav` and `b/done.wav`
+    // collide just like `done.png` and `done.jpg`.
+    let mut by_id: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+    for lookalike in lookalikes {
+        let upper = lookalike.to_ascii_uppercase();
+        for extension in [*lookalike, upper.as_str()] {
+            for path in asset_files(dir, extension, excluded) {
+                // NFC and NFD spellings of a name are the same id.
+                if let Some(id) = path.file_stem().and_then(|stem| stem.to_str()) {
+                    by_id.entry(nfc(id)).or_default().push(path);
+                }
+            }
+        }
+    }
+
+    for (id, mut paths) in by_id {
+        // Shallowest first, the order ids are resolved in.
+        paths.sort_by_cached_key(|path| (path.components().count(), path.clone()));
+        let mut problems = Vec::new();
+        let mut problem = |path: &Path, issue: LintIssue, message: String| {
+            problems.push(LintProblem {
+                kind,
+                id: id.clone(),
+                path: path.to_path_buf(),
+                issue,
+                message,
+            });
+        };
+
+        if paths.len() > 1 {
+            let relative = |path: &Path| {
+                let relative = path.strip_prefix(dir).unwrap_or(path);
+                relative.to_string_lossy().replace('\\', "/")
+            };
+            let names: Vec<String> = paths.iter().map(|path| relative(path)).collect();
+            let used = paths
+                .iter()
+                .find(|path| has_extension(path, kind.extension()));
+            let outcome = match used {
+                Some(used) => format!("only {} is used", relative(used)),
+                None => format!("none of them is a .{} file", kind.extension()),
+            };
+            problem(
+                used.unwrap_or(&paths[0]),
+                LintIssue::NameCollision,
+                format!("id {id} is used by {}; {outcome}", names.join(", ")),
+            );
+        }
+
+        for path in &paths {
+            if !has_extension(path, kind.extension()) {
+                continue;
+            }
+            match kind {
+                AssetKind::Icon => report.icons_checked += 1,
+                AssetKind::Sound => report.sounds_checked += 1,
+            }
+            if let Some((issue, message)) = check_file(kind, path) {
+                problem(path, issue, message);
+            }
+        }
+        report.problems.append(&mut problems);
+    }
+}
+
+fn has_extension(path: &Path, extension: &str) -> bool {
+    path.extension().and_then(|ext| ext.to_str()) == Some(extension)
+}
+
+fn check_file(kind: AssetKind, path: &Path) -> Option<(LintIssue, String)> {
+    let data = match std::fs::read(path) {
+        Ok(data) => data,
+        Err(err) => return Some((LintIssue::Unreadable, err.to_string())),
+    };
+    if data.is_empty() {
+        return Some((LintIssue::EmptyFile, "file is empty".to_string()));
+    }
+
+    match kind {
+        AssetKind::Icon => {
+            let info = match png::parse(&data) {
+                Ok(info) => info,
+                Err(err) => return Some((LintIssue::InvalidPng, err.to_string())),
+            };
+            if info.width > MAX_ICON_DIMENSION
+                || info.height > MAX_ICON_DIMENSION
+                || data.len() as u64 > MAX_ICON_BYTES
+            {
+                return Some((
+                    LintIssue::OversizedIcon,
+                    format!(
+                        "{}x{} px, {} bytes (Windows drops images over {MAX_ICON_DIMENSION}x{MAX_ICON_DIMENSION} px or {MAX_ICON_BYTES} bytes)",
+                        info.width,
+                        info.height,
+                        data.len()
+                    ),
+                ));
+            }
+            if !info.has_alpha() {
+                return Some((
+                    LintIssue::NoAlpha,
+                    "no transparency; the icon will show a solid box on some themes".to_string(),
+                ));
+            }
+            None
+        }
+        AssetKind::Sound => {
+            let info = match wav::parse(&data) {
+                Ok(info) => info,
+                Err(err) => return Some((LintIssue::InvalidWav, err.to_string())),
+            };
+            info.unscalable_reason()
+                .map(|reason| (LintIssue::NotPcm16, reason))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("toastmcp-test-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write(root: &Path, relative: &str, data: &[u8]) {
+        let path = root.join(relative);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, data).unwrap();
+    }
+
+    #[test]
+    fn ids_collide_across_subfolders() {
+        let dir = temp_dir("lint-sounds");
+        let wav = crate::wav::tests::wav_bytes(4, &[0x10, 0, 0x20, 0]);
+        write(&dir, "a/done.wav", &wav);
+        write(&dir, "b/done.wav", &wav);
+        write(&dir, "ok.wav", &wav);
+        write(&dir, "skipped/done.mp3", b"mp3");
+        write(&dir, "skipped/ok.wav", b"");
+
+        let mut report = LintReport::default();
+        lint_dir(
+            AssetKind::Sound,
+            &dir,
+            &["skipped".to_string()],
+            &mut report,
+        );
+        assert_eq!(report.sounds_checked, 3);
+        assert_eq!(report.problems.len(), 1, "{:?}", report.problems);
+        let problem = &report.problems[0];
+        assert_eq!(problem.issue, LintIssue::NameCollision);
+        assert_eq!(problem.id, "done");
+        assert_eq!(problem.path, dir.join("a/done.wav"));
+        assert_eq!(
+            problem.message,
+            "id done is used by a/done.wav, b/done.wav; only a/done.wav is used"
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn lookalikes_in_subfolders_collide_in_nfc() {
+        let dir = temp_dir("lint-icons");
+        let png = crate::png::tests::png_bytes(16, 16, 6, false);
+        write(&dir, "menu/cafe\u{301}.png", &png);
+        write(&dir, "caf\u{e9}.JPG", b"jpeg");
+        write(
+            &dir,
+            "flat.png",
+            &crate::png::tests::png_bytes(16, 16, 2, false),
+        );
+        write(&dir, "photo.gif", b"gif");
+        write(&dir, "old/photo.bmp", b"bmp");
+
+        let mut report = LintReport::default();
+        lint_dir(AssetKind::Icon, &dir, &[], &mut report);
+        assert_eq!(report.icons_checked, 2);
+        let messages: Vec<(LintIssue, &str)> = report
+            .problems
+            .iter()
+            .map(|problem| (problem.issue, problem.message.as_str()))
+            .collect();
+        assert_eq!(messages.len(), 3, "{messages:?}");
+        assert!(messages.contains(&(
+            LintIssue::NameCollision,
+            "id caf\u{e9} is used by caf\u{e9}.JPG, menu/cafe\u{301}.png; only menu/cafe\u{301}.png is used"
+        )));
+        assert!(messages.contains(&(
+            LintIssue::NameCollision,
+            "id photo is used by photo.gif, old/photo.bmp; none of them is a .png file"
+        )));
+        assert!(
+            messages
+                .iter()
+                .any(|(issue, _)| *issue == LintIssue::NoAlpha)
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
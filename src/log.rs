@@ -0,0 +1,123 @@
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Most to least severe; a configured level also lets through everything above it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogLevel {
+    Error,
+    Warn,
+    #[default]
+    Info,
+    Debug,
+    Trace,
+}
+
+struct Logger {
+    file: File,
+    level: LogLevel,
+}
+
+/// The `log_file` log. Lines never go to stdout, which carries the JSON-RPC stream.
+static LOGGER: Mutex<Option<Logger>> = Mutex::new(None);
+
+/// Opens `path` for appending, or turns logging off when `None`. Called again on config reload.
+pub fn init(path: Option<&Path>, level: LogLevel) -> Result<()> {
+    let logger = match path {
+        Some(path) => Some(Logger {
+            file: OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .with_context(|| format!("Failed to open log file {}", path.display()))?,
+            level,
+        }),
+        None => None,
+    };
+    *LOGGER.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = logger;
+    Ok(())
+}
+
+/// Appends one JSON line (`ts`, `level`, `msg`, then `fields`) if a log file is configured
+/// and `level` passes its filter. Write errors are ignored: diagnostics must never take
+/// the server down.
+pub fn write(level: LogLevel, msg: &str, fields: Value) {
+    let mut logger = LOGGER.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let Some(logger) = logger.as_mut().filter(|logger| level <= logger.level) else {
+        return;
+    };
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let mut record = serde_json::json!({
+        "ts": now.as_millis() as f64 / 1000.0,
+        "level": level,
+        "msg": msg,
+    });
+    if let (Some(record), Value::Object(fields)) = (record.as_object_mut(), fields) {
+        record.extend(fields);
+    }
+    let _ = writeln!(logger.file, "{record}");
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::sync::MutexGuard;
+
+    use super::*;
+
+    static LOGGER_TEST: Mutex<()> = Mutex::new(());
+
+    /// Serializes tests that point the global logger somewhere, including ones that
+    /// run `serve`, which re-initializes it from the config.
+    pub(crate) fn lock_logger() -> MutexGuard<'static, ()> {
+        LOGGER_TEST
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    #[test]
+    fn level_filters_less_severe_lines() {
+        let _logger = lock_logger();
+        let path =
+            std::env::temp_dir().join(format!("toastmcp-test-{}-level.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        init(Some(&path), LogLevel::Info).unwrap();
+        write(LogLevel::Debug, "hidden", serde_json::json!({}));
+        write(
+            LogLevel::Info,
+            "shown",
+            serde_json::json!({"method": "ping"}),
+        );
+        write(LogLevel::Warn, "also shown", serde_json::json!({}));
+        init(None, LogLevel::Info).unwrap();
+
+        let lines: Vec<Value> = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["msg"], "shown");
+        assert_eq!(lines[0]["level"], "info");
+        assert_eq!(lines[0]["method"], "ping");
+        assert!(lines[0]["ts"].is_f64());
+        assert_eq!(lines[1]["level"], "warn");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn nothing_is_written_without_a_log_file() {
+        let _logger = lock_logger();
+        init(None, LogLevel::Trace).unwrap();
+        write(LogLevel::Error, "dropped", serde_json::json!({}));
+    }
+}
@@ -1,10 +1,39 @@
 mod assets;
+mod config;
+mod error;
+mod jobs;
+mod lint;
+mod log;
 mod mcp;
 mod notify;
+mod png;
+mod wav;
 
 fn main() {
-    if let Err(err) = mcp::run() {
+    let result = match std::env::args().nth(1).as_deref() {
+        Some("--print-schema") => config::load().and_then(|(config, _)| mcp::print_schema(config)),
+        Some("--config-check") => config::check(),
+        Some("--replay") => replay(),
+        _ => config::load().and_then(|(config, path)| mcp::run(config, path)),
+    };
+    if let Err(err) = result {
         eprintln!("toastmcp error: {err:?}");
         std::process::exit(1);
     }
 }
+
+/// `--replay <file> [output-file]`
+fn replay() -> anyhow::Result<()> {
+    let mut args = std::env::args().skip(2);
+    let input = args
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("Usage: toastmcp --replay <file> [output-file]"))?;
+    let output = args.next();
+    let (config, config_path) = config::load()?;
+    mcp::replay(
+        config,
+        config_path,
+        std::path::Path::new(&input),
+        output.as_deref().map(std::path::Path::new),
+    )
+}
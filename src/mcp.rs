@@ -1,15 +1,37 @@
-use std::io::{self, BufRead, BufReader, Write};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 
 use anyhow::{anyhow, Context, Result};
+use base64::Engine;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
-use crate::assets::{list_icon_ids, list_sound_ids};
-use crate::notify::{notify, NotifyInput};
+use crate::assets::{
+    list_icon_details, list_icon_ids, list_sound_details, list_sound_ids, WINDOWS_SOUND_IDS,
+};
+use crate::config::{self, Config, ConfigHandle};
+use crate::error::NotifyError;
+use crate::jobs::JobQueue;
+use crate::lint::lint_assets;
+use crate::log::{self, LogLevel};
+use crate::notify::{
+    benchmark, cleanup_cache, cleanup_stale_shortcuts, find_sound_path, locate_asset, notify,
+    notify_confirmed, prepare_sound, preview_sound, rename_asset, rename_paths, update_progress,
+    validate_asset_id, validate_benchmark, warm_sound_cache, BenchmarkInput, NotifyInput,
+    NotifyMode, PrepareSoundInput, PreviewSoundInput, RenameAssetInput, ResolveAssetInput,
+    UpdateProgressInput,
+};
 
 const PROTOCOL_VERSION: &str = "2024-11-05";
 const SERVER_NAME: &str = "toastmcp";
 const SERVER_VERSION: &str = env!("CARGO_PKG_VERSION");
+const MAX_MESSAGE_BYTES: usize = 16 * 1024 * 1024;
+const SOUND_DATA_URI_PREFIX: &str = "toastmcp://sound-data/";
+/// Largest WAV served over `resources/read`; base64 grows it by a third.
+const MAX_SOUND_DATA_BYTES: u64 = 4 * 1024 * 1024;
 
 #[derive(Debug, Deserialize)]
 struct RpcRequest {
@@ -40,9 +62,12 @@ struct RpcError {
 #[derive(Debug, Serialize)]
 struct ToolDescription {
     name: &'static str,
-    description: &'static str,
+    description: String,
     #[serde(rename = "inputSchema")]
     input_schema: Value,
+    /// Shape of the tool's `structuredContent`, for tools that return one.
+    #[serde(rename = "outputSchema", skip_serializing_if = "Option::is_none")]
+    output_schema: Option<Value>,
 }
 
 #[derive(Debug, Serialize)]
@@ -54,35 +79,267 @@ struct ResourceDescription {
     mime_type: &'static str,
 }
 
-pub fn run() -> Result<()> {
+struct ServerState {
+    config: ConfigHandle,
+    jobs: JobQueue,
+    defaults: Mutex<SessionDefaults>,
+    /// `TOASTMCP_DRY_RUN=1`: tools with side effects validate and report instead of acting.
+    dry_run: bool,
+}
+
+/// Session-wide defaults set with the `configure` tool. They fill in fields a `notify`
+/// call leaves out and override the config file, but never an explicit argument.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct SessionDefaults {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    icon: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sound: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    volume: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mode: Option<NotifyMode>,
+}
+
+pub fn run(config: Config, config_path: Option<PathBuf>) -> Result<()> {
     let stdin = io::stdin();
-    let mut reader = BufReader::new(stdin.lock());
     let stdout = io::stdout();
-    let mut writer = io::BufWriter::new(stdout.lock());
+    serve(
+        config,
+        config_path,
+        dry_run_requested(),
+        BufReader::new(stdin.lock()),
+        io::BufWriter::new(stdout.lock()),
+    )
+}
+
+/// Runs a captured session from `input` (framed exactly as on the wire) through the
+/// server loop, writing responses to `output` or stdout.
+pub fn replay(
+    config: Config,
+    config_path: Option<PathBuf>,
+    input: &Path,
+    output: Option<&Path>,
+) -> Result<()> {
+    let file = File::open(input)
+        .with_context(|| format!("Failed to open replay file {}", input.display()))?;
+    let reader = BufReader::new(file);
+    match output {
+        Some(path) => {
+            let file = File::create(path)
+                .with_context(|| format!("Failed to create {}", path.display()))?;
+            serve(
+                config,
+                config_path,
+                dry_run_requested(),
+                reader,
+                io::BufWriter::new(file),
+            )
+        }
+        None => serve(
+            config,
+            config_path,
+            dry_run_requested(),
+            reader,
+            io::BufWriter::new(io::stdout().lock()),
+        ),
+    }
+}
+
+/// `TOASTMCP_DRY_RUN=1`.
+fn dry_run_requested() -> bool {
+    std::env::var("TOASTMCP_DRY_RUN").is_ok_and(|value| value == "1")
+}
+
+/// Serves requests until `reader` runs dry. A `dry_run` server leaves the filesystem
+/// alone: no sound warmup, shortcut cleanup or cache cleanup, and a read-only config.
+fn serve(
+    config: Config,
+    config_path: Option<PathBuf>,
+    dry_run: bool,
+    reader: impl BufRead,
+    writer: impl Write,
+) -> Result<()> {
+    let forced_framing = framing_override()?;
+    serve_framed(config, config_path, dry_run, forced_framing, reader, writer)
+}
+
+/// `serve`, answering in `forced_framing` when set instead of mirroring each request.
+fn serve_framed(
+    mut config: Config,
+    config_path: Option<PathBuf>,
+    dry_run: bool,
+    forced_framing: Option<Framing>,
+    mut reader: impl BufRead,
+    mut writer: impl Write,
+) -> Result<()> {
+    log::init(config.log_file.as_deref(), config.log_level)?;
+    config.read_only = dry_run;
+    if config.warmup_sounds && !dry_run {
+        let warmup_config = config.clone();
+        std::thread::spawn(move || warm_sound_cache(&warmup_config));
+    }
+    if !dry_run {
+        let display_name = config.display_name.clone();
+        std::thread::spawn(move || match cleanup_stale_shortcuts(&display_name) {
+            Ok(0) => {}
+            Ok(removed) => eprintln!("toastmcp: removed {removed} stale Start Menu shortcut(s)"),
+            Err(err) => eprintln!("toastmcp: stale shortcut cleanup failed: {err:#}"),
+        });
+    }
+    let state = ServerState {
+        config: ConfigHandle::new(config, config_path),
+        jobs: JobQueue::spawn(),
+        defaults: Mutex::new(SessionDefaults::default()),
+        dry_run,
+    };
+    let pretty = std::env::var("TOASTMCP_PRETTY").is_ok_and(|value| value == "1");
 
     loop {
-        let message = match read_message(&mut reader)? {
-            Some(message) => message,
-            None => break,
+        let message = match read_message(&mut reader, MAX_MESSAGE_BYTES) {
+            Ok(Some(message)) => message,
+            Ok(None) => break,
+            Err(err) => {
+                // The oversized message has already been skipped, so the stream is back
+                // on a message boundary: reject it and keep serving.
+                let Some(too_large) = err.downcast_ref::<MessageTooLarge>().copied() else {
+                    return Err(err);
+                };
+                log::write(
+                    LogLevel::Warn,
+                    "message too large",
+                    serde_json::json!({
+                        "framing": format!("{:?}", too_large.framing),
+                        "limit": too_large.limit
+                    }),
+                );
+                let response = error_response(Value::Null, -32600, too_large.to_string());
+                let framing = forced_framing.unwrap_or(too_large.framing);
+                if !send(&mut writer, &response, framing, pretty)? {
+                    break;
+                }
+                continue;
+            }
         };
+        log::write(
+            LogLevel::Trace,
+            "message",
+            serde_json::json!({
+                "framing": format!("{:?}", message.framing),
+                "bytes": message.bytes,
+                "batch": message.is_batch()
+            }),
+        );
+        if message.is_batch() {
+            let response = error_response(
+                Value::Null,
+                -32600,
+                "Batch requests are not supported; send one request per message".to_string(),
+            );
+            let framing = forced_framing.unwrap_or(message.framing);
+            if !send(&mut writer, &response, framing, pretty)? {
+                break;
+            }
+            continue;
+        }
         let request: RpcRequest = serde_json::from_str(&message.payload)
             .with_context(|| format!("Invalid JSON-RPC payload: {}", message.payload))?;
-        if let Some(response) = handle_request(request)? {
-            write_message(&mut writer, &response, message.framing)?;
+        let (method, id) = (request.method.clone(), request.id.clone());
+        let correlation_id = correlation_id(&request.params);
+        let started = std::time::Instant::now();
+        let response = handle_request(request, &state)?;
+        log_request(
+            &method,
+            id,
+            correlation_id,
+            response.as_ref(),
+            started.elapsed(),
+        );
+        if let Some(response) = response {
+            let framing = forced_framing.unwrap_or(message.framing);
+            if !send(&mut writer, &response, framing, pretty)? {
+                break;
+            }
         }
     }
 
+    let config = state.config.current();
+    if config.cleanup_on_exit && !dry_run {
+        cleanup_cache(config.cleanup_sound_cache);
+    }
+
     Ok(())
 }
 
-fn handle_request(request: RpcRequest) -> Result<Option<RpcResponse>> {
+/// Writes one response, returning `false` once the host has closed its end of stdout:
+/// nobody is listening, so the caller shuts down quietly.
+fn send(
+    writer: &mut impl Write,
+    response: &RpcResponse,
+    framing: Framing,
+    pretty: bool,
+) -> Result<bool> {
+    match write_message(writer, response, framing, pretty) {
+        Ok(()) => Ok(true),
+        Err(err) if is_closed_pipe(&err) => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+/// Logs one handled request. Failures (a JSON-RPC error or a tool result with
+/// `isError`) are logged at `warn`, everything else at `info`.
+fn log_request(
+    method: &str,
+    id: Option<Value>,
+    correlation_id: Option<String>,
+    response: Option<&RpcResponse>,
+    elapsed: std::time::Duration,
+) {
+    let rpc_error = response.and_then(|response| response.error.as_ref());
+    let result = response.and_then(|response| response.result.as_ref());
+    let tool_failed = result
+        .and_then(|result| result.get("isError"))
+        .and_then(Value::as_bool)
+        .unwrap_or(false);
+    let error_code = match rpc_error {
+        Some(error) => Value::from(error.code),
+        None => result
+            .and_then(|result| result.pointer("/structuredContent/errorCode"))
+            .cloned()
+            .unwrap_or(Value::Null),
+    };
+    let level = if rpc_error.is_some() || tool_failed {
+        LogLevel::Warn
+    } else {
+        LogLevel::Info
+    };
+
+    let mut fields = serde_json::json!({
+        "method": method,
+        "id": id,
+        "duration_ms": elapsed.as_secs_f64() * 1000.0,
+    });
+    if !error_code.is_null() {
+        fields["errorCode"] = error_code;
+    }
+    if let Some(correlation_id) = correlation_id {
+        fields["correlation_id"] = Value::from(correlation_id);
+    }
+    log::write(level, "request", fields);
+}
+
+fn handle_request(request: RpcRequest, state: &ServerState) -> Result<Option<RpcResponse>> {
     match request.method.as_str() {
         "initialize" => Ok(Some(handle_initialize(request))),
-        "tools/list" => Ok(Some(handle_tools_list(request))),
-        "tools/call" => Ok(Some(handle_tools_call(request))),
+        "tools/list" => Ok(Some(handle_tools_list(request, state))),
+        "tools/call" => Ok(Some(handle_tools_call(request, state))),
+        "completion/complete" => Ok(Some(handle_completion_complete(request, state))),
         "resources/list" => Ok(Some(handle_resources_list(request))),
-        "resources/read" => Ok(Some(handle_resources_read(request))),
-        "resource-templates/list" => Ok(Some(handle_resource_templates_list(request))),
+        "resources/read" => Ok(Some(handle_resources_read(request, state))),
+        "resources/templates/list" | "resource-templates/list" => {
+            Ok(Some(handle_resource_templates_list(request)))
+        }
         "ping" => Ok(Some(ok_response(request, Value::Null))),
         _ => {
             if let Some(id) = request.id {
@@ -112,7 +369,8 @@ fn handle_initialize(request: RpcRequest) -> RpcResponse {
             "protocolVersion": protocol_version,
             "capabilities": {
                 "tools": {},
-                "resources": {}
+                "resources": {},
+                "completions": {}
             },
             "serverInfo": {
                 "name": SERVER_NAME,
@@ -122,73 +380,485 @@ fn handle_initialize(request: RpcRequest) -> RpcResponse {
     )
 }
 
-fn handle_tools_list(request: RpcRequest) -> RpcResponse {
-    let icon_ids = list_icon_ids();
-    let sound_ids = list_sound_ids();
+fn handle_tools_list(request: RpcRequest, state: &ServerState) -> RpcResponse {
+    ok_response(
+        request,
+        serde_json::json!({
+            "tools": tool_descriptions(&state.config.current())
+        }),
+    )
+}
+
+/// Every tool ToastMCP exposes, with input schemas built from the current (allowed) assets.
+fn tool_descriptions(config: &Config) -> Vec<ToolDescription> {
+    let icon_ids = list_icon_ids(config);
+    let sound_ids = list_sound_ids(config);
+    // Lets the model see how much it has to work with without calling list_assets.
+    let asset_counts = format!(
+        "({} icons, {} sounds available; call list_assets for ids)",
+        icon_ids.len(),
+        sound_ids.len()
+    );
     let icon_schema = if icon_ids.is_empty() {
         serde_json::json!({
             "type": "string",
-            "description": "Required. Icon id from icons/ folder (without extension). Do not guess; add icons or call tools/list for the current enum."
+            "description": "Required unless set with configure. Icon id from icons/ folder (without extension). Do not guess; add icons or call tools/list for the current enum."
         })
     } else {
         serde_json::json!({
             "type": "string",
             "enum": icon_ids,
-            "description": "Required. Must be one of the enum values (no guessing)."
+            "description": "Required unless set with configure. Must be one of the enum values (no guessing)."
         })
     };
     let sound_schema = if sound_ids.is_empty() {
         serde_json::json!({
             "type": "string",
-            "description": "Required. Sound id from sounds/ folder (without extension). Do not guess; add sounds or call tools/list for the current enum."
+            "description": "Required unless set with configure or beep_sequence is given. Sound id from sounds/ folder (without extension). Do not guess; add sounds or call tools/list for the current enum."
         })
     } else {
         serde_json::json!({
             "type": "string",
-            "enum": sound_ids,
-            "description": "Required. Must be one of the enum values (no guessing)."
+            "anyOf": [
+                { "enum": sound_ids },
+                { "pattern": "[*?]" }
+            ],
+            "description": "Required unless set with configure or beep_sequence is given. Must be one of the enum values (no guessing), or a glob such as \"chime*\" to play a random matching sound. A glob with a / matches paths under sounds/, so \"chimes/*\" picks from the chimes subfolder."
         })
     };
 
-    let tool = ToolDescription {
-        name: "notify",
-        description: "Send a system toast + sound. Use only the provided icon/sound ids (no guessing); call tools/list to see the current enums.",
-        input_schema: serde_json::json!({
-            "type": "object",
-            "additionalProperties": false,
-            "properties": {
-                "title": {
-                    "type": "string",
-                    "description": "Very short description of the current project (5 words or less)."
+    let notify_schema = serde_json::json!({
+        "type": "object",
+        "additionalProperties": false,
+        "properties": {
+            "title": {
+                "type": "string",
+                "description": "Very short description of the current project (5 words or less)."
+            },
+            "message": {
+                "type": "string",
+                "description": "Body text. Newlines become separate lines; toasts show at most 2. Omit when using summary/detail."
+            },
+            "summary": {
+                "type": "string",
+                "description": "Optional. Short banner line (cut at 100 characters) shown above detail. Use instead of message for long-form content."
+            },
+            "detail": {
+                "type": "string",
+                "description": "Optional. Full text shown after the summary; Windows clips it in the banner and shows it all when the Action Center entry is expanded."
+            },
+            "body_max_lines": {
+                "type": "integer",
+                "minimum": 1,
+                "maximum": 4,
+                "description": "Optional. Wrap each body line to at most this many lines before truncating (hint-maxLines). Out-of-range values are clamped."
+            },
+            "sound": sound_schema,
+            "icon": icon_schema,
+            "mode": {
+                "type": "string",
+                "enum": ["normal", "log_only"],
+                "description": "Optional. \"log_only\" adds a silent Action Center entry with no banner and no sound."
+            },
+            "sound_kind": {
+                "type": "string",
+                "enum": ["auto", "file", "system"],
+                "description": "Optional. Force a WAV from sounds/ (\"file\") or a Windows system sound (\"system\") when both share an id. Default \"auto\" tries the file first."
+            },
+            "strip_markdown": {
+                "type": "boolean",
+                "description": "Optional. Remove Markdown syntax (**, *, `, #, [links](...)) from the message before display."
+            },
+            "auto_remove_secs": {
+                "type": "integer",
+                "minimum": 1,
+                "maximum": 259200,
+                "description": "Optional. Seconds until the toast expires; Windows then also clears it from the Action Center. Pair with mode \"log_only\" for transient entries."
+            },
+            "data": {
+                "type": "object",
+                "additionalProperties": { "type": "string" },
+                "description": "Optional. Small key-value payload returned in the activation arguments when the toast is clicked (URL-encoded into the toast's launch string, max 2048 bytes)."
+            },
+            "icon_bg": {
+                "type": "string",
+                "pattern": "^#[0-9A-Fa-f]{6}$",
+                "description": "Optional. Background color (\"#RRGGBB\") placed behind transparent icons so they read well on any theme."
+            },
+            "thread": {
+                "type": "object",
+                "additionalProperties": false,
+                "properties": {
+                    "id": {
+                        "type": "string",
+                        "minLength": 1,
+                        "maxLength": 64,
+                        "description": "Stable id shared by every message in the conversation."
+                    },
+                    "title": {
+                        "type": "string",
+                        "description": "Conversation name, shown as a small attribution line on each toast."
+                    }
                 },
-                "message": { "type": "string" },
-                "sound": sound_schema,
-                "icon": icon_schema
+                "required": ["id", "title"],
+                "description": "Optional. Groups related messages from a chat-like agent as one conversation."
             },
-            "required": ["title", "message", "sound", "icon"]
-        }),
-    };
+            "tag": {
+                "type": "string",
+                "minLength": 1,
+                "maxLength": 64,
+                "description": "Optional. A new toast with the same tag (and thread) replaces this one. Required with progress."
+            },
+            "audio_loop": {
+                "type": "boolean",
+                "description": "Optional. Loop a WAV sound (e.g. for alarms) until audio_max_secs pass or the next notification is shown. Not for system sounds."
+            },
+            "audio_max_secs": {
+                "type": "integer",
+                "minimum": 1,
+                "maximum": 300,
+                "description": "Optional. With audio_loop, stop after this many seconds (default 15)."
+            },
+            "toast_id": {
+                "type": "string",
+                "minLength": 1,
+                "maxLength": 64,
+                "description": "Optional. Sets hint-toastId: a later toast with the same id collapses into this one in the Action Center instead of stacking. Unlike tag, it doesn't replace the popup."
+            },
+            "show_relative_time": {
+                "type": "boolean",
+                "description": "Optional. Stamp the toast with its send time so the Action Center shows it as \"x minutes ago\"."
+            },
+            "no_cache": {
+                "type": "boolean",
+                "description": "Optional. Re-render the volume-scaled WAV instead of using the cached copy."
+            },
+            "beep_sequence": {
+                "type": "array",
+                "minItems": 1,
+                "maxItems": 16,
+                "items": {
+                    "type": "object",
+                    "additionalProperties": false,
+                    "properties": {
+                        "freq": { "type": "integer", "minimum": 37, "maximum": 32767, "description": "Hz." },
+                        "ms": { "type": "integer", "minimum": 1, "maximum": 2000 }
+                    },
+                    "required": ["freq", "ms"]
+                },
+                "description": "Optional. Play these tones instead of sound, for a distinct cue without a WAV (5 seconds total at most). Not affected by volume."
+            },
+            "verbose": {
+                "type": "boolean",
+                "description": "Optional. Include per-phase timings (ms) in the result."
+            },
+            "progress": {
+                "type": "object",
+                "additionalProperties": false,
+                "properties": {
+                    "value": {
+                        "anyOf": [
+                            { "type": "number", "minimum": 0, "maximum": 1 },
+                            { "const": "indeterminate" }
+                        ],
+                        "description": "A fraction from 0.0 to 1.0 (1 is a full bar), or \"indeterminate\" for a spinner. Give this or percent."
+                    },
+                    "percent": { "type": "integer", "minimum": 0, "maximum": 100, "description": "A whole percent from 0 to 100. Give this or value." },
+                    "status": { "type": "string", "description": "Short label under the bar, e.g. \"Compiling...\"." }
+                },
+                "oneOf": [{ "required": ["value"] }, { "required": ["percent"] }],
+                "description": "Optional. Show a progress bar; change it later with update_progress using the same tag."
+            }
+        },
+        "required": ["title"]
+    });
 
-    ok_response(
-        request,
-        serde_json::json!({
-            "tools": [
-                tool,
+    let notify_output_schema = serde_json::json!({
+        "type": "object",
+        "properties": {
+            "sent": { "type": "boolean" },
+            "sound": {
+                "type": "string",
+                "description": "The sound picked when sound was a glob."
+            },
+            "warnings": { "type": "array", "items": { "type": "string" } },
+            "timings": {
+                "type": "object",
+                "description": "Milliseconds per phase, with verbose.",
+                "additionalProperties": { "type": "number" }
+            },
+            "dry_run": { "type": "boolean" },
+            "plan": {
+                "type": "object",
+                "description": "Under TOASTMCP_DRY_RUN, the toast and sound that would have been delivered."
+            },
+            "error": { "type": "string" },
+            "errorCode": {
+                "type": "string",
+                "enum": ["audio_decode", "platform_unsupported", "toast_too_large", "notify_failed"]
+            }
+        },
+        "required": ["sent"]
+    });
+
+    // Plain ids, or `AssetInfo` objects with `detailed: true`.
+    let asset_list_schema = serde_json::json!({
+        "type": "array",
+        "items": {
+            "anyOf": [
+                { "type": "string" },
                 {
-                    "name": "list_assets",
-                    "description": "List available icon and sound ids for ToastMCP.",
-                    "inputSchema": {
-                        "type": "object",
-                        "additionalProperties": false,
-                        "properties": {}
-                    }
+                    "type": "object",
+                    "properties": {
+                        "id": { "type": "string" },
+                        "size": { "type": "integer", "description": "Bytes." },
+                        "modified": { "type": "integer", "description": "Unix seconds." }
+                    },
+                    "required": ["id"]
                 }
             ]
-        }),
-    )
+        }
+    });
+
+    vec![
+        ToolDescription {
+            name: "notify",
+            description: format!(
+                "Send a system toast + sound. Use only the provided icon/sound ids (no guessing); call tools/list to see the current enums. {asset_counts}"
+            ),
+            input_schema: notify_schema.clone(),
+            output_schema: Some(notify_output_schema),
+        },
+        ToolDescription {
+            name: "notify_async",
+            description: "Queue a notify call and return a job id immediately. Same arguments as notify; check the outcome with notify_status.".into(),
+            input_schema: notify_schema.clone(),
+            output_schema: None,
+        },
+        ToolDescription {
+            name: "notify_confirmed",
+            description: "Send a notification like notify, then check the Action Center history for its tag to confirm it landed. Returns shown and history_count (null if the history can't be read). A tag is generated when none is given.".into(),
+            input_schema: notify_schema.clone(),
+            output_schema: None,
+        },
+        ToolDescription {
+            name: "describe_notification",
+            description: "Validate notify arguments and return the resolved toast (session defaults applied, icon and sound resolved, body split into lines) as structured JSON, without showing or playing anything.".into(),
+            input_schema: notify_schema,
+            output_schema: None,
+        },
+        ToolDescription {
+            name: "notify_status",
+            description: "Report the outcome of a notify_async job: pending, succeeded, or failed.".into(),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "additionalProperties": false,
+                "properties": {
+                    "job_id": { "type": "integer", "minimum": 1 }
+                },
+                "required": ["job_id"]
+            }),
+            output_schema: None,
+        },
+        ToolDescription {
+            name: "list_assets",
+            description: "List available icon and sound ids for ToastMCP.".into(),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "additionalProperties": false,
+                "properties": {
+                    "detailed": {
+                        "type": "boolean",
+                        "description": "Optional. Return each asset as {id, size, modified} (bytes, Unix seconds) instead of a plain id."
+                    }
+                }
+            }),
+            output_schema: Some(serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "icons": asset_list_schema.clone(),
+                    "sounds": asset_list_schema
+                },
+                "required": ["icons", "sounds"]
+            })),
+        },
+        ToolDescription {
+            name: "benchmark",
+            description: "Measure toast delivery latency: shows N silent, popup-suppressed toasts sharing a tag and reports min/avg/max/p95 milliseconds.".into(),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "additionalProperties": false,
+                "properties": {
+                    "count": { "type": "integer", "minimum": 1, "maximum": 100, "default": 10 },
+                    "tag": { "type": "string", "default": "toastmcp-benchmark" },
+                    "load_xml": { "type": "boolean", "default": false, "description": "Parse the full toast XML for every toast instead of filling the cached template, to compare the two." }
+                }
+            }),
+            output_schema: None,
+        },
+        ToolDescription {
+            name: "prepare_sound",
+            description: "Run a WAV sound through the volume pipeline without playing it. Returns the file that would be played and whether scaling was applied or skipped (and why).".into(),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "additionalProperties": false,
+                "properties": {
+                    "sound": { "type": "string", "description": "Sound id from sounds/ (without extension)." },
+                    "volume": { "type": "number", "minimum": 0.0, "maximum": 1.0, "description": "Optional. Defaults to the configured volume." },
+                    "force": { "type": "boolean", "description": "Optional. Regenerate the scaled copy even if a cached one exists." }
+                },
+                "required": ["sound"]
+            }),
+            output_schema: None,
+        },
+        ToolDescription {
+            name: "preview_sound",
+            description: "Play a WAV sound through the volume pipeline without showing a toast, e.g. to compare loudness levels. Returns once playback starts, with the file played.".into(),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "additionalProperties": false,
+                "properties": {
+                    "sound": { "type": "string", "description": "Sound id from sounds/ (without extension)." },
+                    "volume": { "type": "number", "minimum": 0.0, "maximum": 1.0, "description": "Optional. Defaults to the configured volume." }
+                },
+                "required": ["sound"]
+            }),
+            output_schema: None,
+        },
+        ToolDescription {
+            name: "rename_asset",
+            description: "Rename an icon or sound file in its assets folder and clear cache entries derived from the old name.".into(),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "additionalProperties": false,
+                "properties": {
+                    "kind": { "type": "string", "enum": ["icon", "sound"] },
+                    "from": { "type": "string", "description": "Current asset id (without extension)." },
+                    "to": { "type": "string", "description": "New asset id (without extension). Must not already exist." }
+                },
+                "required": ["kind", "from", "to"]
+            }),
+            output_schema: None,
+        },
+        ToolDescription {
+            name: "resolve_asset",
+            description: "Report which file an icon or sound id resolves to, and every path searched in order, without showing or playing anything.".into(),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "additionalProperties": false,
+                "properties": {
+                    "kind": { "type": "string", "enum": ["icon", "sound"] },
+                    "id": { "type": "string", "description": "Asset id (without extension)." }
+                },
+                "required": ["kind", "id"]
+            }),
+            output_schema: None,
+        },
+        ToolDescription {
+            name: "reload_config",
+            description: "Re-read the ToastMCP config file and apply it without restarting. Returns the new effective config.".into(),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "additionalProperties": false,
+                "properties": {}
+            }),
+            output_schema: None,
+        },
+        ToolDescription {
+            name: "get_config",
+            description: "Return the effective configuration: the loaded config file (or defaults) with session defaults from configure applied, the file it came from, the session defaults, and environment switches.".into(),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "additionalProperties": false,
+                "properties": {}
+            }),
+            output_schema: None,
+        },
+        ToolDescription {
+            name: "update_progress",
+            description: "Update the progress bar of a toast sent with progress and a tag, in place and without re-showing it.".into(),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "additionalProperties": false,
+                "properties": {
+                    "tag": { "type": "string", "description": "The tag the toast was sent with." },
+                    "group": { "type": "string", "description": "Optional. The thread id, if the toast was sent with a thread." },
+                    "value": {
+                        "anyOf": [
+                            { "type": "number", "minimum": 0, "maximum": 1 },
+                            { "const": "indeterminate" }
+                        ],
+                        "description": "A fraction from 0.0 to 1.0 (1 is a full bar), or \"indeterminate\". Give this or percent."
+                    },
+                    "percent": { "type": "integer", "minimum": 0, "maximum": 100, "description": "A whole percent from 0 to 100. Give this or value." },
+                    "status": { "type": "string", "description": "Optional. New status label; unchanged when omitted." }
+                },
+                "required": ["tag"],
+                "oneOf": [{ "required": ["value"] }, { "required": ["percent"] }]
+            }),
+            output_schema: None,
+        },
+        ToolDescription {
+            name: "lint_assets",
+            description: "Audit every icon and sound file and report problems: empty or unreadable files, PNGs without transparency, oversized icons, WAVs that can't be volume-scaled, and ids shared by several files.".into(),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "additionalProperties": false,
+                "properties": {}
+            }),
+            output_schema: None,
+        },
+        ToolDescription {
+            name: "configure",
+            description: "Set session defaults (icon, sound, volume, mode) used when a notify call leaves them out. Explicit notify arguments always win. Returns the current defaults.".into(),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "additionalProperties": false,
+                "properties": {
+                    "icon": {
+                        "type": "string",
+                        "description": "Optional. Default icon id; must be an existing icon."
+                    },
+                    "sound": {
+                        "type": "string",
+                        "description": "Optional. Default sound id; must be an existing sound."
+                    },
+                    "volume": {
+                        "type": "number",
+                        "minimum": 0,
+                        "maximum": 1,
+                        "description": "Optional. Playback volume (0.0-1.0) overriding the config file."
+                    },
+                    "mode": {
+                        "type": "string",
+                        "enum": ["normal", "log_only"],
+                        "description": "Optional. Default mode; \"log_only\" makes the session silent."
+                    },
+                    "reset": {
+                        "type": "boolean",
+                        "description": "Optional. Clear all defaults before applying the others."
+                    }
+                }
+            }),
+            output_schema: None,
+        },
+    ]
 }
 
-fn handle_tools_call(request: RpcRequest) -> RpcResponse {
+/// Prints each tool's `inputSchema`, keyed by tool name, without starting the protocol loop.
+pub fn print_schema(config: Config) -> Result<()> {
+    let schemas: serde_json::Map<String, Value> = tool_descriptions(&config)
+        .into_iter()
+        .map(|tool| (tool.name.to_string(), tool.input_schema))
+        .collect();
+    let stdout = io::stdout();
+    let mut writer = stdout.lock();
+    serde_json::to_writer_pretty(&mut writer, &schemas)?;
+    writer.write_all(b"\n")?;
+    Ok(())
+}
+
+fn handle_tools_call(request: RpcRequest, state: &ServerState) -> RpcResponse {
     let Some(id) = request.id else {
         return error_response(
             Value::Null,
@@ -203,59 +873,540 @@ fn handle_tools_call(request: RpcRequest) -> RpcResponse {
         .and_then(Value::as_str)
         .unwrap_or("");
 
-    if name == "list_assets" {
-        let icons = list_icon_ids();
-        let sounds = list_sound_ids();
-        let result = serde_json::json!({
-            "content": [
-                {"type": "text", "text": serde_json::json!({"icons": icons, "sounds": sounds}).to_string()}
-            ]
-        });
-        return RpcResponse {
-            jsonrpc: "2.0",
-            id,
-            result: Some(result),
-            error: None,
-        };
-    }
+    let correlation_id = correlation_id(&request.params);
+    let result = match name {
+        // A dry run has no delivery to wait for, so notify_async answers like notify.
+        "notify_async" if state.dry_run => call_notify(&request.params, state),
+        "benchmark" | "prepare_sound" | "preview_sound" | "rename_asset" | "update_progress"
+            if state.dry_run =>
+        {
+            call_dry_run(name, &request.params, state)
+        }
+        "notify" => call_notify(&request.params, state),
+        "notify_async" => call_notify_async(&request.params, state),
+        "notify_status" => call_notify_status(&request.params, state),
+        "notify_confirmed" => call_notify_confirmed(&request.params, state),
+        "list_assets" => call_list_assets(&request.params, state),
+        "reload_config" => Ok(call_reload_config(state)),
+        "get_config" => Ok(call_get_config(state)),
+        "describe_notification" => call_describe_notification(&request.params, state),
+        "benchmark" => call_benchmark(&request.params, state),
+        "prepare_sound" => call_prepare_sound(&request.params, state),
+        "preview_sound" => call_preview_sound(&request.params, state),
+        "rename_asset" => call_rename_asset(&request.params, state),
+        "resolve_asset" => call_resolve_asset(&request.params, state),
+        "configure" => call_configure(&request.params, state),
+        "update_progress" => call_update_progress(&request.params, state),
+        "lint_assets" => Ok(structured_result(
+            serde_json::to_value(lint_assets(&state.config.current())).unwrap_or_default(),
+        )),
+        _ => Err(format!("Unknown tool: {name}")),
+    };
 
-    if name != "notify" {
-        return error_response(id, -32602, format!("Unknown tool: {name}"));
+    match result {
+        Ok(mut result) => {
+            if let (Some(correlation_id), Some(result)) = (correlation_id, result.as_object_mut()) {
+                result.insert(
+                    "_meta".to_string(),
+                    serde_json::json!({"correlation_id": correlation_id}),
+                );
+            }
+            RpcResponse {
+                jsonrpc: "2.0",
+                id,
+                result: Some(result),
+                error: None,
+            }
+        }
+        Err(message) => error_response(id, -32602, message),
     }
+}
 
-    let args_value = request
-        .params
-        .get("arguments")
-        .cloned()
-        .unwrap_or(Value::Null);
+/// The caller's `_meta.correlation_id`, echoed back in the result and used to tag log lines.
+fn correlation_id(params: &Value) -> Option<String> {
+    params
+        .get("_meta")
+        .and_then(|meta| meta.get("correlation_id"))
+        .and_then(Value::as_str)
+        .map(str::to_string)
+}
+
+fn call_notify(params: &Value, state: &ServerState) -> Result<Value, String> {
+    let (args, config) = notify_arguments(params, state)?;
+    Ok(match notify(args, &config) {
+        Ok(outcome) => {
+            let sent = outcome.plan.is_none();
+            let mut text = if sent {
+                "Notification sent.".to_string()
+            } else {
+                "Dry run: nothing was shown or played.".to_string()
+            };
+            if let Some(sound) = &outcome.sound {
+                text.push_str(&format!("\nSound: {sound}"));
+            }
+            if let Some(timings) = &outcome.timings {
+                text.push_str(&format!(
+                    "\nTimings (ms): {}",
+                    serde_json::to_string(timings).unwrap_or_default()
+                ));
+            }
+            for warning in &outcome.warnings {
+                text.push_str(&format!("\nWarning: {warning}"));
+            }
+            if let Some(plan) = &outcome.plan {
+                text.push_str(&format!(
+                    "\nPlan: {}",
+                    serde_json::to_string(plan).unwrap_or_default()
+                ));
+            }
+            let mut structured = serde_json::to_value(&outcome).unwrap_or_default();
+            structured["sent"] = Value::Bool(sent);
+            if !sent {
+                structured["dry_run"] = Value::Bool(true);
+            }
+            let mut result = text_result(text);
+            result["structuredContent"] = structured;
+            result
+        }
+        Err(err) => notify_error_result(&err),
+    })
+}
 
-    let args: NotifyInput = match serde_json::from_value(args_value) {
-        Ok(args) => args,
+fn call_describe_notification(params: &Value, state: &ServerState) -> Result<Value, String> {
+    let (mut args, config) = notify_arguments(params, state)?;
+    args.dry_run = true;
+    Ok(match notify(args, &config) {
+        Ok(outcome) => {
+            let structured = serde_json::json!({
+                "valid": true,
+                "spec": outcome.plan,
+                "sound": outcome.sound,
+                "warnings": outcome.warnings
+            });
+            let mut result = text_result(serde_json::to_string(&structured).unwrap_or_default());
+            result["structuredContent"] = structured;
+            result
+        }
         Err(err) => {
-            return error_response(id, -32602, format!("Invalid arguments: {err}"));
+            let code = err
+                .downcast_ref::<NotifyError>()
+                .map_or("invalid_notification", NotifyError::code);
+            let mut result = error_result(format!("Invalid notification: {err}"));
+            result["structuredContent"] = serde_json::json!({
+                "valid": false,
+                "error": err.to_string(),
+                "errorCode": code
+            });
+            result
         }
+    })
+}
+
+fn notify_error_result(err: &anyhow::Error) -> Value {
+    let code = err
+        .downcast_ref::<NotifyError>()
+        .map_or("notify_failed", NotifyError::code);
+    let mut result = error_result(format!("Notification failed: {err}"));
+    result["structuredContent"] = serde_json::json!({
+        "sent": false,
+        "error": err.to_string(),
+        "errorCode": code
+    });
+    result
+}
+
+fn call_notify_confirmed(params: &Value, state: &ServerState) -> Result<Value, String> {
+    let (args, config) = notify_arguments(params, state)?;
+    Ok(match notify_confirmed(args, &config) {
+        Ok(confirmed) => structured_result(serde_json::to_value(&confirmed).unwrap_or_default()),
+        Err(err) => notify_error_result(&err),
+    })
+}
+
+fn call_notify_async(params: &Value, state: &ServerState) -> Result<Value, String> {
+    let (args, config) = notify_arguments(params, state)?;
+    let job_id = state.jobs.submit(args, config);
+    Ok(text_result(
+        serde_json::json!({"job_id": job_id, "status": "pending"}).to_string(),
+    ))
+}
+
+#[derive(Debug, Deserialize)]
+struct NotifyStatusInput {
+    job_id: u64,
+}
+
+fn call_notify_status(params: &Value, state: &ServerState) -> Result<Value, String> {
+    let args: NotifyStatusInput = parse_arguments(params)?;
+    let Some(status) = state.jobs.status(args.job_id) else {
+        return Ok(error_result(format!("Unknown job id: {}", args.job_id)));
     };
+    let mut payload = serde_json::json!({"job_id": args.job_id});
+    if let (Some(payload), Value::Object(status)) = (
+        payload.as_object_mut(),
+        serde_json::to_value(status).unwrap_or_default(),
+    ) {
+        payload.extend(status);
+    }
+    Ok(text_result(payload.to_string()))
+}
 
-    let result = match notify(args) {
-        Ok(()) => serde_json::json!({
-            "content": [
-                {"type": "text", "text": "Notification sent."}
-            ]
-        }),
-        Err(err) => serde_json::json!({
-            "content": [
-                {"type": "text", "text": format!("Notification failed: {err}") }
-            ],
-            "isError": true
-        }),
+#[derive(Debug, Deserialize)]
+struct ListAssetsInput {
+    #[serde(default)]
+    detailed: bool,
+}
+
+fn call_list_assets(params: &Value, state: &ServerState) -> Result<Value, String> {
+    let args: ListAssetsInput = parse_arguments(params)?;
+    let config = state.config.current();
+    let payload = if args.detailed {
+        serde_json::json!({
+            "icons": list_icon_details(&config),
+            "sounds": list_sound_details(&config)
+        })
+    } else {
+        serde_json::json!({"icons": list_icon_ids(&config), "sounds": list_sound_ids(&config)})
     };
+    Ok(structured_result(payload))
+}
 
-    RpcResponse {
-        jsonrpc: "2.0",
-        id,
-        result: Some(result),
-        error: None,
+/// The settings `notify` runs with right now: the loaded config with session
+/// defaults applied, plus where it came from and the env switches in effect.
+fn call_get_config(state: &ServerState) -> Value {
+    let defaults = lock_defaults(state).clone();
+    let mut config = (*state.config.current()).clone();
+    if let Some(volume) = defaults.volume {
+        config.volume = volume;
+    }
+    structured_result(serde_json::json!({
+        "config": config,
+        "file": state.config.path(),
+        "session_defaults": defaults,
+        "environment": {
+            "dry_run": state.dry_run,
+            "framing": std::env::var("TOASTMCP_FRAMING").ok(),
+            "pretty": std::env::var("TOASTMCP_PRETTY").is_ok_and(|value| value == "1")
+        }
+    }))
+}
+
+fn call_reload_config(state: &ServerState) -> Value {
+    match config::load() {
+        Ok((mut config, path)) => {
+            if let Err(err) = log::init(config.log_file.as_deref(), config.log_level) {
+                return error_result(format!("Config reload failed: {err:#}"));
+            }
+            config.read_only = state.dry_run;
+            let config = state.config.replace(config, path);
+            text_result(serde_json::json!({"config": &*config}).to_string())
+        }
+        Err(err) => error_result(format!("Config reload failed: {err:#}")),
+    }
+}
+
+fn call_benchmark(params: &Value, state: &ServerState) -> Result<Value, String> {
+    let args: BenchmarkInput = parse_arguments(params)?;
+    let config = state.config.current();
+    Ok(match benchmark(args, &config) {
+        Ok(report) => text_result(serde_json::to_string(&report).unwrap_or_default()),
+        Err(err) => error_result(format!("Benchmark failed: {err}")),
+    })
+}
+
+fn call_prepare_sound(params: &Value, state: &ServerState) -> Result<Value, String> {
+    let args: PrepareSoundInput = parse_arguments(params)?;
+    let config = state.config.current();
+    Ok(match prepare_sound(args, &config) {
+        Ok(prepared) => text_result(serde_json::to_string(&prepared).unwrap_or_default()),
+        Err(err) => error_result(format!("Sound preparation failed: {err}")),
+    })
+}
+
+fn call_preview_sound(params: &Value, state: &ServerState) -> Result<Value, String> {
+    let args: PreviewSoundInput = parse_arguments(params)?;
+    let config = state.config.current();
+    Ok(match preview_sound(args, &config) {
+        Ok(prepared) => text_result(serde_json::to_string(&prepared).unwrap_or_default()),
+        Err(err) => error_result(format!("Sound preview failed: {err}")),
+    })
+}
+
+fn call_update_progress(params: &Value, state: &ServerState) -> Result<Value, String> {
+    let args: UpdateProgressInput = parse_arguments(params)?;
+    let config = state.config.current();
+    Ok(match update_progress(args, &config) {
+        Ok(true) => text_result("Progress updated."),
+        Ok(false) => text_result(format!(
+            "Progress update coalesced; the latest value is shown within {} ms.",
+            config.progress_debounce_ms
+        )),
+        Err(err) => error_result(format!("Progress update failed: {err}")),
+    })
+}
+
+/// Dry-run stand-in for the tools other than notify that change something: checks
+/// the arguments like the real call and reports what it would have done.
+fn call_dry_run(name: &str, params: &Value, state: &ServerState) -> Result<Value, String> {
+    // The server's config is read-only, so preparing a sound only reports the cache file.
+    let would = match name {
+        "prepare_sound" => {
+            let args: PrepareSoundInput = parse_arguments(params)?;
+            prepare_sound(args, &state.config.current())
+                .map(|prepared| serde_json::json!({"prepare": prepared}))
+        }
+        "preview_sound" => {
+            let args: PreviewSoundInput = parse_arguments(params)?;
+            let prepare = PrepareSoundInput {
+                sound: args.sound,
+                volume: args.volume,
+                force: false,
+            };
+            prepare_sound(prepare, &state.config.current())
+                .map(|prepared| serde_json::json!({"play": prepared}))
+        }
+        "benchmark" => {
+            let args: BenchmarkInput = parse_arguments(params)?;
+            validate_benchmark(&args).map(|()| {
+                serde_json::json!({
+                    "show_silent_toasts": args.count,
+                    "tag": args.tag,
+                    "load_xml": args.load_xml
+                })
+            })
+        }
+        "rename_asset" => {
+            let args: RenameAssetInput = parse_arguments(params)?;
+            rename_paths(&args, &state.config.current())
+                .map(|(from, to)| serde_json::json!({"rename": from, "to": to}))
+        }
+        "update_progress" => {
+            let args: UpdateProgressInput = parse_arguments(params)?;
+            args.amount().map(|value| {
+                serde_json::json!({
+                    "update_tag": args.tag,
+                    "group": args.group,
+                    "value": value,
+                    "status": args.status
+                })
+            })
+        }
+        _ => return Err(format!("Unknown tool: {name}")),
+    };
+    Ok(match would {
+        Ok(would) => structured_result(serde_json::json!({"dry_run": true, "would": would})),
+        Err(err) => error_result(format!("Dry run of {name} failed: {err}")),
+    })
+}
+
+fn call_rename_asset(params: &Value, state: &ServerState) -> Result<Value, String> {
+    let args: RenameAssetInput = parse_arguments(params)?;
+    Ok(match rename_asset(args, &state.config.current()) {
+        Ok(renamed) => text_result(serde_json::to_string(&renamed).unwrap_or_default()),
+        Err(err) => error_result(format!("Rename failed: {err}")),
+    })
+}
+
+fn call_resolve_asset(params: &Value, state: &ServerState) -> Result<Value, String> {
+    let args: ResolveAssetInput = parse_arguments(params)?;
+    let resolved = match locate_asset(args, &state.config.current()) {
+        Ok(resolved) => resolved,
+        Err(err) => return Ok(error_result(format!("Resolve failed: {err}"))),
+    };
+    let payload = serde_json::to_string(&resolved).unwrap_or_default();
+    Ok(match resolved.path {
+        Some(_) => text_result(payload),
+        None => error_result(format!(
+            "No {}/{}.{} found. Searched: {payload}",
+            resolved.kind.folder(),
+            resolved.id,
+            resolved.kind.extension()
+        )),
+    })
+}
+
+/// Parses `notify` arguments, filling omitted fields from the session defaults, and
+/// returns the config to use with any default volume applied.
+fn notify_arguments(
+    params: &Value,
+    state: &ServerState,
+) -> Result<(NotifyInput, Arc<Config>), String> {
+    let defaults = lock_defaults(state).clone();
+    let mut params = params.clone();
+    if let Some(params) = params.as_object_mut() {
+        let arguments = params
+            .entry("arguments")
+            .or_insert_with(|| Value::Object(Default::default()));
+        if arguments.is_null() {
+            *arguments = Value::Object(Default::default());
+        }
+        if let Some(arguments) = arguments.as_object_mut() {
+            let fallbacks = [
+                ("icon", defaults.icon.map(Value::from)),
+                ("sound", defaults.sound.map(Value::from)),
+                ("mode", defaults.mode.and_then(|mode| serde_json::to_value(mode).ok())),
+            ];
+            for (key, value) in fallbacks {
+                if let Some(value) = value
+                    && !arguments.contains_key(key)
+                {
+                    arguments.insert(key.to_string(), value);
+                }
+            }
+        }
+    }
+
+    let mut args: NotifyInput = parse_arguments(&params)?;
+    args.correlation_id = correlation_id(&params);
+    args.dry_run = state.dry_run;
+    let mut config = state.config.current();
+    if let Some(volume) = defaults.volume {
+        let mut overridden = (*config).clone();
+        overridden.volume = volume;
+        config = Arc::new(overridden);
+    }
+    Ok((args, config))
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigureInput {
+    #[serde(default)]
+    reset: bool,
+    #[serde(flatten)]
+    defaults: SessionDefaults,
+}
+
+fn call_configure(params: &Value, state: &ServerState) -> Result<Value, String> {
+    let args: ConfigureInput = parse_arguments(params)?;
+    let config = state.config.current();
+    let update = args.defaults;
+
+    if let Some(icon) = update.icon.as_deref()
+        && !list_icon_ids(&config).iter().any(|id| id == icon)
+    {
+        return Ok(error_result(format!(
+            "Unknown icon: {icon}. Use tools/list for the current ids."
+        )));
+    }
+    if let Some(sound) = update.sound.as_deref()
+        && !list_sound_ids(&config).iter().any(|id| id == sound)
+        && !(WINDOWS_SOUND_IDS.contains(&sound) && config.allows_sound(sound))
+    {
+        return Ok(error_result(format!(
+            "Unknown sound: {sound}. Use tools/list for the current ids."
+        )));
+    }
+    if let Some(volume) = update.volume
+        && !(0.0..=1.0).contains(&volume)
+    {
+        return Ok(error_result(format!(
+            "volume must be between 0.0 and 1.0 (got {volume})"
+        )));
+    }
+
+    let mut defaults = lock_defaults(state);
+    if args.reset {
+        *defaults = SessionDefaults::default();
+    }
+    if update.icon.is_some() {
+        defaults.icon = update.icon;
+    }
+    if update.sound.is_some() {
+        defaults.sound = update.sound;
+    }
+    if update.volume.is_some() {
+        defaults.volume = update.volume;
     }
+    if update.mode.is_some() {
+        defaults.mode = update.mode;
+    }
+    Ok(text_result(
+        serde_json::json!({"defaults": &*defaults}).to_string(),
+    ))
+}
+
+fn lock_defaults(state: &ServerState) -> std::sync::MutexGuard<'_, SessionDefaults> {
+    state
+        .defaults
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn parse_arguments<T: DeserializeOwned>(params: &Value) -> Result<T, String> {
+    let args_value = match params.get("arguments") {
+        Some(Value::Null) | None => Value::Object(Default::default()),
+        Some(args) => args.clone(),
+    };
+    serde_json::from_value(args_value).map_err(|err| format!("Invalid arguments: {err}"))
+}
+
+fn text_result(text: impl Into<String>) -> Value {
+    serde_json::json!({
+        "content": [
+            {"type": "text", "text": text.into()}
+        ]
+    })
+}
+
+/// Returns `payload` as `structuredContent`, plus the same JSON as text for clients
+/// that don't read structured results.
+fn structured_result(payload: Value) -> Value {
+    serde_json::json!({
+        "content": [
+            {"type": "text", "text": payload.to_string()}
+        ],
+        "structuredContent": payload
+    })
+}
+
+fn error_result(text: impl Into<String>) -> Value {
+    serde_json::json!({
+        "content": [
+            {"type": "text", "text": text.into()}
+        ],
+        "isError": true
+    })
+}
+
+/// Completes `icon` and `sound` argument values from the current assets, by prefix.
+fn handle_completion_complete(request: RpcRequest, state: &ServerState) -> RpcResponse {
+    const MAX_COMPLETIONS: usize = 100;
+
+    let argument = request.params.get("argument");
+    let name = argument
+        .and_then(|argument| argument.get("name"))
+        .and_then(Value::as_str)
+        .unwrap_or("");
+    let partial = argument
+        .and_then(|argument| argument.get("value"))
+        .and_then(Value::as_str)
+        .unwrap_or("")
+        .to_lowercase();
+
+    let config = state.config.current();
+    let candidates = match name {
+        "icon" => list_icon_ids(&config),
+        "sound" => list_sound_ids(&config),
+        _ => Vec::new(),
+    };
+    let matches: Vec<String> = candidates
+        .into_iter()
+        .filter(|id| id.to_lowercase().starts_with(&partial))
+        .collect();
+    let total = matches.len();
+    let values: Vec<String> = matches.into_iter().take(MAX_COMPLETIONS).collect();
+
+    ok_response(
+        request,
+        serde_json::json!({
+            "completion": {
+                "values": values,
+                "total": total,
+                "hasMore": total > MAX_COMPLETIONS
+            }
+        }),
+    )
 }
 
 fn handle_resources_list(request: RpcRequest) -> RpcResponse {
@@ -278,17 +1429,39 @@ fn handle_resource_templates_list(request: RpcRequest) -> RpcResponse {
     ok_response(
         request,
         serde_json::json!({
-            "resourceTemplates": []
+            "resourceTemplates": [{
+                "uriTemplate": format!("{SOUND_DATA_URI_PREFIX}{{id}}"),
+                "name": "ToastMCP sound data",
+                "description": "Base64-encoded WAV bytes of a sound from sounds/, for previewing.",
+                "mimeType": "audio/wav"
+            }]
         }),
     )
 }
 
-fn handle_resources_read(request: RpcRequest) -> RpcResponse {
+fn handle_resources_read(request: RpcRequest, state: &ServerState) -> RpcResponse {
     let uri = request
         .params
         .get("uri")
         .and_then(Value::as_str)
-        .unwrap_or("");
+        .unwrap_or("")
+        .to_string();
+
+    if let Some(sound_id) = uri.strip_prefix(SOUND_DATA_URI_PREFIX) {
+        return match read_sound_data(&percent_decode(sound_id), &state.config.current()) {
+            Ok(blob) => ok_response(
+                request,
+                serde_json::json!({
+                    "contents": [{
+                        "uri": uri,
+                        "mimeType": "audio/wav",
+                        "blob": blob
+                    }]
+                }),
+            ),
+            Err(err) => error_response(request.id.unwrap_or(Value::Null), -32602, err),
+        };
+    }
 
     if uri != "toastmcp://assets" {
         return error_response(
@@ -298,8 +1471,9 @@ fn handle_resources_read(request: RpcRequest) -> RpcResponse {
         );
     }
 
-    let icons = list_icon_ids();
-    let sounds = list_sound_ids();
+    let config = state.config.current();
+    let icons = list_icon_ids(&config);
+    let sounds = list_sound_ids(&config);
     let payload = serde_json::json!({
         "icons": icons,
         "sounds": sounds
@@ -318,6 +1492,51 @@ fn handle_resources_read(request: RpcRequest) -> RpcResponse {
     )
 }
 
+/// Decodes `%XX` escapes, so ids with spaces can be written as `Level%20Up` in a URI.
+/// Malformed escapes are kept as they are.
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| text.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                out.push(byte);
+                i += 3;
+            }
+            None => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+fn read_sound_data(sound_id: &str, config: &Config) -> Result<String, String> {
+    validate_asset_id(sound_id).map_err(|err| err.to_string())?;
+    if !config.allows_sound(sound_id) {
+        return Err(format!("Sound {sound_id} is not allowed by this server's config"));
+    }
+    let path =
+        find_sound_path(sound_id, config).ok_or_else(|| format!("Sound not found: {sound_id}"))?;
+    let size = std::fs::metadata(&path)
+        .map_err(|err| format!("Failed to read sound {sound_id}: {err}"))?
+        .len();
+    if size > MAX_SOUND_DATA_BYTES {
+        return Err(format!(
+            "Sound {sound_id} is {size} bytes; sound data is limited to {MAX_SOUND_DATA_BYTES} bytes"
+        ));
+    }
+    let bytes =
+        std::fs::read(&path).map_err(|err| format!("Failed to read sound {sound_id}: {err}"))?;
+    Ok(base64::engine::general_purpose::STANDARD.encode(bytes))
+}
+
 fn ok_response(request: RpcRequest, result: Value) -> RpcResponse {
     RpcResponse {
         jsonrpc: "2.0",
@@ -342,56 +1561,170 @@ enum Framing {
     JsonLine,
 }
 
+/// `TOASTMCP_FRAMING=lsp|jsonline` forces the response framing for clients that
+/// don't accept their own framing mirrored back. Unset (or `auto`) mirrors each request.
+fn framing_override() -> Result<Option<Framing>> {
+    parse_framing(std::env::var("TOASTMCP_FRAMING").ok().as_deref())
+}
+
+fn parse_framing(value: Option<&str>) -> Result<Option<Framing>> {
+    let Some(value) = value else {
+        return Ok(None);
+    };
+    match value.trim().to_ascii_lowercase().as_str() {
+        "" | "auto" => Ok(None),
+        "lsp" => Ok(Some(Framing::Lsp)),
+        "jsonline" => Ok(Some(Framing::JsonLine)),
+        _ => Err(anyhow!(
+            "Invalid TOASTMCP_FRAMING {value:?}: expected lsp, jsonline or auto"
+        )),
+    }
+}
+
 struct IncomingMessage {
     payload: String,
     framing: Framing,
+    /// Size on the wire, excluding framing headers and line endings.
+    bytes: usize,
 }
 
-fn read_message(reader: &mut impl BufRead) -> Result<Option<IncomingMessage>> {
+impl IncomingMessage {
+    /// JSON-RPC batches are arrays; ToastMCP answers them with an error and only handles
+    /// single requests.
+    fn is_batch(&self) -> bool {
+        self.payload.trim_start().starts_with('[')
+    }
+}
+
+/// A message over the size limit. By the time this is returned its bytes have been
+/// read and discarded, so the next read starts on the following message.
+#[derive(Debug, Clone, Copy)]
+struct MessageTooLarge {
+    framing: Framing,
+    limit: usize,
+}
+
+impl std::fmt::Display for MessageTooLarge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Message exceeds the {} byte message limit", self.limit)
+    }
+}
+
+impl std::error::Error for MessageTooLarge {}
+
+fn read_message(reader: &mut impl BufRead, limit: usize) -> Result<Option<IncomingMessage>> {
     let mut content_length: Option<usize> = None;
-    let mut line = String::new();
+    let mut line = Vec::new();
 
     loop {
-        line.clear();
-        let bytes = reader.read_line(&mut line)?;
+        let bytes = read_line_capped(reader, &mut line, limit)?;
         if bytes == 0 {
             return Ok(None);
         }
-        let trimmed = line.trim_end_matches(['\r', '\n']);
-        if trimmed.starts_with('{') && trimmed.contains("\"jsonrpc\"") {
+        let line = std::str::from_utf8(&line).context("Message is not valid UTF-8")?;
+        let trimmed = strip_bom(line).trim_end_matches(['\r', '\n']);
+        if trimmed.starts_with(['{', '[']) && trimmed.contains("\"jsonrpc\"") {
             return Ok(Some(IncomingMessage {
                 payload: trimmed.to_string(),
                 framing: Framing::JsonLine,
+                bytes: trimmed.len(),
             }));
         }
         if trimmed.is_empty() {
             break;
         }
-        if let Some((name, value)) = trimmed.split_once(':') {
-            if name.trim().eq_ignore_ascii_case("content-length") {
-                content_length = Some(
-                    value
-                        .trim()
-                        .parse::<usize>()
-                        .context("Invalid Content-Length header")?,
-                );
-            }
+        if let Some((name, value)) = trimmed.split_once(':')
+            && name.trim().eq_ignore_ascii_case("content-length")
+        {
+            content_length = Some(
+                value
+                    .trim()
+                    .parse::<usize>()
+                    .context("Invalid Content-Length header")?,
+            );
         }
     }
 
     let length = content_length.ok_or_else(|| anyhow!("Missing Content-Length header"))?;
+    if length > limit {
+        io::copy(&mut (&mut *reader).take(length as u64), &mut io::sink())?;
+        return Err(MessageTooLarge {
+            framing: Framing::Lsp,
+            limit,
+        }
+        .into());
+    }
     let mut buf = vec![0u8; length];
     reader.read_exact(&mut buf)?;
     let payload = String::from_utf8(buf).context("Payload is not valid UTF-8")?;
     Ok(Some(IncomingMessage {
-        payload,
+        payload: strip_bom(&payload).to_string(),
         framing: Framing::Lsp,
+        bytes: length,
     }))
 }
 
+/// Some Windows clients prefix their JSON with a UTF-8 byte order mark.
+fn strip_bom(text: &str) -> &str {
+    text.strip_prefix('\u{feff}').unwrap_or(text)
+}
+
+/// Reads one `\n`-terminated line into `buf` without letting it grow past `limit` bytes.
+/// A longer line is discarded through its newline and reported as `MessageTooLarge`.
+fn read_line_capped(reader: &mut impl BufRead, buf: &mut Vec<u8>, limit: usize) -> Result<usize> {
+    buf.clear();
+    let mut oversized = false;
+    loop {
+        let available = reader.fill_buf()?;
+        if available.is_empty() && !oversized {
+            return Ok(buf.len());
+        }
+        let (used, done) = match available.iter().position(|&b| b == b'\n') {
+            Some(pos) => (pos + 1, true),
+            None => (available.len(), available.is_empty()),
+        };
+        if !oversized && buf.len() + used > limit {
+            oversized = true;
+            buf.clear();
+        }
+        if !oversized {
+            buf.extend_from_slice(&available[..used]);
+        }
+        reader.consume(used);
+        if done && oversized {
+            return Err(MessageTooLarge {
+                framing: Framing::JsonLine,
+                limit,
+            }
+            .into());
+        }
+        if done {
+            return Ok(buf.len());
+        }
+    }
+}
+
+fn is_closed_pipe(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<io::Error>().is_some_and(|err| {
+        matches!(
+            err.kind(),
+            io::ErrorKind::BrokenPipe | io::ErrorKind::WriteZero
+        )
+    })
+}
 
-fn write_message(writer: &mut impl Write, response: &RpcResponse, framing: Framing) -> Result<()> {
-    let payload = serde_json::to_string(response)?;
+/// `pretty` only applies to LSP framing: a multi-line payload would break
+/// line-delimited JSON, while Content-Length is measured on the bytes actually sent.
+fn write_message(
+    writer: &mut impl Write,
+    response: &RpcResponse,
+    framing: Framing,
+    pretty: bool,
+) -> Result<()> {
+    let payload = match framing {
+        Framing::Lsp if pretty => serde_json::to_string_pretty(response)?,
+        _ => serde_json::to_string(response)?,
+    };
     match framing {
         Framing::Lsp => {
             write!(writer, "Content-Length: {}\r\n\r\n", payload.len())?;
@@ -405,3 +1738,718 @@ fn write_message(writer: &mut impl Write, response: &RpcResponse, framing: Frami
     writer.flush()?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn serve_input(input: &str) -> Vec<Value> {
+        serve_with(Config::default(), input)
+    }
+
+    fn serve_with(config: Config, input: &str) -> Vec<Value> {
+        serve_dry_run(config, false, input)
+    }
+
+    fn serve_dry_run(config: Config, dry_run: bool, input: &str) -> Vec<Value> {
+        let _logger = log::tests::lock_logger();
+        let mut output = Vec::new();
+        serve(config, None, dry_run, input.as_bytes(), &mut output).unwrap();
+        String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    fn call_tool(name: &str, arguments: Value) -> Value {
+        call_tool_with(false, name, arguments)
+    }
+
+    fn call_tool_with(dry_run: bool, name: &str, arguments: Value) -> Value {
+        let request = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "tools/call",
+            "params": {"name": name, "arguments": arguments}
+        });
+        let mut responses = serve_dry_run(Config::default(), dry_run, &format!("{request}\n"));
+        assert_eq!(responses.len(), 1);
+        responses.remove(0)["result"].take()
+    }
+
+    fn test_state(config: Config, config_path: Option<PathBuf>) -> ServerState {
+        ServerState {
+            config: ConfigHandle::new(config, config_path),
+            jobs: JobQueue::spawn(),
+            defaults: Mutex::new(SessionDefaults::default()),
+            dry_run: false,
+        }
+    }
+
+    #[test]
+    fn get_config_reports_the_loaded_file() {
+        let state = test_state(Config::default(), Some(PathBuf::from("loaded.toml")));
+        let result = call_get_config(&state);
+        assert_eq!(result["structuredContent"]["file"], "loaded.toml");
+
+        state.config.replace(Config::default(), None);
+        let result = call_get_config(&state);
+        assert_eq!(result["structuredContent"]["file"], Value::Null);
+    }
+
+    #[test]
+    fn lint_assets_returns_structured_content() {
+        let result = call_tool("lint_assets", serde_json::json!({}));
+        let report = &result["structuredContent"];
+        assert!(report["icons_checked"].is_u64());
+        assert!(report["problems"].is_array());
+    }
+
+    #[test]
+    fn correlation_id_is_logged_with_the_request() {
+        let log_file = std::env::temp_dir().join(format!(
+            "toastmcp-test-{}-correlation.log",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&log_file);
+        let config = Config {
+            log_file: Some(log_file.clone()),
+            log_level: LogLevel::Trace,
+            ..Config::default()
+        };
+        let request = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 7,
+            "method": "tools/call",
+            "params": {
+                "name": "lint_assets",
+                "arguments": {},
+                "_meta": {"correlation_id": "run-42"}
+            }
+        });
+        let responses = serve_with(config, &format!("{request}\n"));
+        assert_eq!(responses[0]["result"]["_meta"]["correlation_id"], "run-42");
+
+        let log = std::fs::read_to_string(&log_file).unwrap();
+        let request_line = log
+            .lines()
+            .map(|line| serde_json::from_str::<Value>(line).unwrap())
+            .find(|line| line["msg"] == "request")
+            .unwrap();
+        assert_eq!(request_line["correlation_id"], "run-42");
+        assert_eq!(request_line["id"], 7);
+        std::fs::remove_file(&log_file).unwrap();
+    }
+
+    #[test]
+    fn oversized_line_is_rejected_and_serving_continues() {
+        let oversized = format!(
+            "{{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"ping\",\"params\":{{\"pad\":\"{}\"}}}}\n",
+            "x".repeat(MAX_MESSAGE_BYTES)
+        );
+        let input = format!("{oversized}{{\"jsonrpc\":\"2.0\",\"id\":2,\"method\":\"ping\"}}\n");
+        let responses = serve_input(&input);
+        assert_eq!(responses.len(), 2);
+        assert_eq!(responses[0]["id"], Value::Null);
+        assert_eq!(responses[0]["error"]["code"], -32600);
+        assert_eq!(responses[1]["id"], 2);
+        assert_eq!(responses[1]["result"], Value::Null);
+        assert!(responses[1].get("error").is_none());
+    }
+
+    #[test]
+    fn oversized_line_is_drained_to_its_newline() {
+        let input = "{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"ping\"}\n\
+                     {\"jsonrpc\":\"2.0\",\"id\":2,\"method\":\"ping\"}\n";
+        let mut reader = io::BufReader::with_capacity(4, input.as_bytes());
+        let err = read_message(&mut reader, 16).err().unwrap();
+        assert!(err.downcast_ref::<MessageTooLarge>().is_some());
+        let err = read_message(&mut reader, 16).err().unwrap();
+        assert!(err.downcast_ref::<MessageTooLarge>().is_some());
+        assert!(read_message(&mut reader, 16).unwrap().is_none());
+
+        let mut reader = io::BufReader::with_capacity(4, input.as_bytes());
+        let _ = read_message(&mut reader, 16);
+        let message = read_message(&mut reader, 1024).unwrap().unwrap();
+        assert!(message.payload.contains("\"id\":2"));
+    }
+
+    #[test]
+    fn oversized_content_length_is_skipped() {
+        let big = format!(
+            "{{\"jsonrpc\":\"2.0\",\"id\":1,\"pad\":\"{}\"}}",
+            "x".repeat(64)
+        );
+        let small = "{\"jsonrpc\":\"2.0\",\"id\":2,\"method\":\"ping\"}";
+        let input = format!(
+            "Content-Length: {}\r\n\r\n{big}Content-Length: {}\r\n\r\n{small}",
+            big.len(),
+            small.len()
+        );
+        let mut reader = input.as_bytes();
+        let err = read_message(&mut reader, 64).err().unwrap();
+        let too_large = err.downcast_ref::<MessageTooLarge>().unwrap();
+        assert!(matches!(too_large.framing, Framing::Lsp));
+        let message = read_message(&mut reader, 64).unwrap().unwrap();
+        assert_eq!(message.payload, small);
+        assert!(matches!(message.framing, Framing::Lsp));
+    }
+
+    #[test]
+    fn framing_override_accepts_lsp_jsonline_or_auto() {
+        assert!(parse_framing(None).unwrap().is_none());
+        assert!(parse_framing(Some("")).unwrap().is_none());
+        assert!(parse_framing(Some("auto")).unwrap().is_none());
+        assert!(matches!(
+            parse_framing(Some(" LSP ")).unwrap(),
+            Some(Framing::Lsp)
+        ));
+        assert!(matches!(
+            parse_framing(Some("jsonline")).unwrap(),
+            Some(Framing::JsonLine)
+        ));
+        let err = parse_framing(Some("http")).unwrap_err().to_string();
+        assert!(err.contains("Invalid TOASTMCP_FRAMING \"http\""), "{err}");
+    }
+
+    #[test]
+    fn forced_framing_wins_over_the_request_framing() {
+        let ping = r#"{"jsonrpc":"2.0","id":1,"method":"ping"}"#;
+        let framed_ping = format!("Content-Length: {}\r\n\r\n{ping}", ping.len());
+        let respond = |input: &str, forced| {
+            let _logger = log::tests::lock_logger();
+            let mut output = Vec::new();
+            serve_framed(
+                Config::default(),
+                None,
+                false,
+                forced,
+                input.as_bytes(),
+                &mut output,
+            )
+            .unwrap();
+            String::from_utf8(output).unwrap()
+        };
+
+        let mirrored = respond(&format!("{ping}\n"), None);
+        assert!(mirrored.starts_with("{"), "{mirrored}");
+        let forced = respond(&format!("{ping}\n"), Some(Framing::Lsp));
+        assert!(forced.starts_with("Content-Length: "), "{forced}");
+
+        let mirrored = respond(&framed_ping, None);
+        assert!(mirrored.starts_with("Content-Length: "), "{mirrored}");
+        let forced = respond(&framed_ping, Some(Framing::JsonLine));
+        assert!(
+            forced.starts_with("{") && forced.ends_with("}\n"),
+            "{forced}"
+        );
+    }
+
+    #[test]
+    fn bom_prefixed_initialize_is_served_in_either_framing() {
+        let initialize = r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{}}"#;
+        let bom = '\u{feff}';
+        let with_payload_bom = format!("{bom}{initialize}");
+        let inputs = [
+            format!("{bom}{initialize}\n"),
+            format!(
+                "{bom}Content-Length: {}\r\n\r\n{initialize}",
+                initialize.len()
+            ),
+            format!(
+                "Content-Length: {}\r\n\r\n{with_payload_bom}",
+                with_payload_bom.len()
+            ),
+        ];
+        for input in inputs {
+            let _logger = log::tests::lock_logger();
+            let mut output = Vec::new();
+            serve(
+                Config::default(),
+                None,
+                false,
+                input.as_bytes(),
+                &mut output,
+            )
+            .unwrap();
+            let output = String::from_utf8(output).unwrap();
+            let body = &output[output.find('{').unwrap()..];
+            let response: Value = serde_json::from_str(body.trim_end()).unwrap();
+            assert_eq!(response["id"], 1, "{input:?}");
+            assert_eq!(
+                response["result"]["serverInfo"]["name"], SERVER_NAME,
+                "{input:?}"
+            );
+            let lsp = input.contains("Content-Length");
+            assert_eq!(output.starts_with("Content-Length: "), lsp, "{input:?}");
+        }
+    }
+
+    #[test]
+    fn trace_log_records_framing_size_and_batches() {
+        let log_file =
+            std::env::temp_dir().join(format!("toastmcp-test-{}-framing.log", std::process::id()));
+        let _ = std::fs::remove_file(&log_file);
+        let config = Config {
+            log_file: Some(log_file.clone()),
+            log_level: LogLevel::Trace,
+            ..Config::default()
+        };
+        let line = r#"{"jsonrpc":"2.0","id":1,"method":"ping"}"#;
+        let framed = r#"{"jsonrpc":"2.0","id":2,"method":"ping"}"#;
+        let batch = r#"[{"jsonrpc":"2.0","id":3,"method":"ping"}]"#;
+        let input = format!(
+            "{line}\nContent-Length: {}\r\n\r\n{framed}{batch}\n",
+            framed.len()
+        );
+        let mut output = Vec::new();
+        {
+            let _logger = log::tests::lock_logger();
+            serve(config, None, false, input.as_bytes(), &mut output).unwrap();
+            log::init(None, LogLevel::Trace).unwrap();
+        }
+        let output = String::from_utf8(output).unwrap();
+        assert!(!output.contains("framing"));
+        // The batch's answer follows the unterminated Content-Length response.
+        let last = output.lines().last().unwrap();
+        let rejected: Value =
+            serde_json::from_str(&last[last.rfind("{\"jsonrpc\"").unwrap()..]).unwrap();
+        assert_eq!(rejected["id"], Value::Null);
+        assert_eq!(rejected["error"]["code"], -32600);
+
+        let messages: Vec<Value> = std::fs::read_to_string(&log_file)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str::<Value>(line).unwrap())
+            .filter(|line| line["msg"] == "message")
+            .collect();
+        std::fs::remove_file(&log_file).unwrap();
+        let seen: Vec<(&str, u64, bool)> = messages
+            .iter()
+            .map(|message| {
+                let framing = message["framing"].as_str().unwrap();
+                (
+                    framing,
+                    message["bytes"].as_u64().unwrap(),
+                    message["batch"] == true,
+                )
+            })
+            .collect();
+        assert_eq!(
+            seen,
+            [
+                ("JsonLine", line.len() as u64, false),
+                ("Lsp", framed.len() as u64, false),
+                ("JsonLine", batch.len() as u64, true),
+            ]
+        );
+    }
+
+    #[test]
+    fn dry_run_replay_leaves_the_filesystem_alone() {
+        let exe_dir = std::env::current_exe()
+            .unwrap()
+            .parent()
+            .unwrap()
+            .to_path_buf();
+        let id = format!("toastmcp-test-{}-dry-run", std::process::id());
+        let cache = exe_dir.join("cache");
+        std::fs::create_dir_all(&cache).unwrap();
+        let _fixture = crate::assets::tests::SoundFixture::new(&id);
+        let sentinel = cache.join(format!("{id}-sentinel.txt"));
+        std::fs::write(&sentinel, b"keep").unwrap();
+
+        let config = Config {
+            volume: 0.33,
+            warmup_sounds: true,
+            cleanup_on_exit: true,
+            cleanup_sound_cache: true,
+            ..Config::default()
+        };
+        let requests = [
+            serde_json::json!({"name": "prepare_sound", "arguments": {"sound": id, "volume": 0.37}}),
+            serde_json::json!({"name": "notify", "arguments": {
+                "title": "Dry", "message": "run", "icon": "happy", "sound": id, "icon_bg": "#123456"
+            }}),
+            serde_json::json!({"name": "preview_sound", "arguments": {"sound": id}}),
+        ];
+        let input: String = requests
+            .iter()
+            .enumerate()
+            .map(|(index, params)| {
+                let request = serde_json::json!({
+                    "jsonrpc": "2.0", "id": index, "method": "tools/call", "params": params
+                });
+                format!("{request}\n")
+            })
+            .collect();
+        let responses = serve_dry_run(config, true, &input);
+        // A warmup thread would have been spawned by now; give it time to write.
+        std::thread::sleep(std::time::Duration::from_millis(200));
+
+        let prepared = &responses[0]["result"]["structuredContent"];
+        assert_eq!(prepared["dry_run"], true);
+        let planned = cache.join(format!("{id}_vol37.wav"));
+        assert_eq!(
+            prepared["would"]["prepare"]["path"],
+            planned.to_str().unwrap()
+        );
+        assert_eq!(responses[1]["result"]["structuredContent"]["sent"], false);
+        assert_eq!(responses[2]["result"]["structuredContent"]["dry_run"], true);
+
+        let written: Vec<_> = std::fs::read_dir(&cache)
+            .unwrap()
+            .flatten()
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .filter(|name| name.starts_with(&id))
+            .collect();
+        std::fs::remove_file(&sentinel).unwrap();
+        assert_eq!(written, [format!("{id}-sentinel.txt")]);
+    }
+
+    #[test]
+    fn describe_notification_reports_oversized_toasts_as_invalid() {
+        let arguments = |message: String| serde_json::json!({"title": "Big", "message": message, "icon": "happy", "sound": "default"});
+        let result = call_tool("describe_notification", arguments("x".repeat(10_000)));
+        assert_eq!(result["isError"], true);
+        let structured = &result["structuredContent"];
+        assert_eq!(structured["valid"], false);
+        assert_eq!(structured["errorCode"], "toast_too_large");
+
+        let result = call_tool("describe_notification", arguments("fits".to_string()));
+        assert_eq!(result["structuredContent"]["valid"], true);
+        assert_eq!(result["structuredContent"]["spec"]["body"][0], "fits");
+    }
+
+    #[test]
+    fn multi_megabyte_messages_under_the_cap_are_read_whole() {
+        let payload = format!(
+            "{{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"ping\",\"pad\":\"{}\"}}",
+            "x".repeat(3 * 1024 * 1024)
+        );
+        let line = format!("{payload}\n");
+        let message = read_message(&mut line.as_bytes(), MAX_MESSAGE_BYTES)
+            .unwrap()
+            .unwrap();
+        assert!(matches!(message.framing, Framing::JsonLine));
+        assert_eq!(message.payload, payload);
+
+        let framed = format!("Content-Length: {}\r\n\r\n{payload}", payload.len());
+        let message = read_message(&mut framed.as_bytes(), payload.len())
+            .unwrap()
+            .unwrap();
+        assert!(matches!(message.framing, Framing::Lsp));
+        assert_eq!(message.bytes, payload.len());
+    }
+
+    #[test]
+    fn list_assets_details_only_when_asked() {
+        let plain = call_tool("list_assets", serde_json::json!({}));
+        assert!(plain["structuredContent"]["icons"][0].is_string());
+
+        let detailed = call_tool("list_assets", serde_json::json!({"detailed": true}));
+        let icons = detailed["structuredContent"]["icons"].as_array().unwrap();
+        let happy = icons.iter().find(|icon| icon["id"] == "happy").unwrap();
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("icons")
+            .join("happy.png");
+        assert_eq!(happy["size"], std::fs::metadata(path).unwrap().len());
+        assert!(happy["modified"].is_u64());
+    }
+
+    #[test]
+    fn notify_returns_structured_content_alongside_text() {
+        let arguments = serde_json::json!({
+            "title": "Done", "message": "Build passed", "icon": "happy", "sound": "default"
+        });
+        let result = call_tool_with(true, "notify", arguments.clone());
+        assert_ne!(result["isError"], true);
+        assert!(result["content"][0]["text"].is_string());
+        let structured = &result["structuredContent"];
+        assert_eq!(structured["sent"], false);
+        assert_eq!(structured["dry_run"], true);
+        assert_eq!(structured["plan"]["title"], "Done");
+        assert_eq!(structured["plan"]["body"][0], "Build passed");
+
+        let mut too_big = arguments;
+        too_big["message"] = Value::String("x".repeat(10_000));
+        let result = call_tool_with(true, "notify", too_big);
+        assert_eq!(result["isError"], true);
+        let structured = &result["structuredContent"];
+        assert_eq!(structured["sent"], false);
+        assert_eq!(structured["errorCode"], "toast_too_large");
+        assert!(structured["error"].as_str().unwrap().contains("limit"));
+    }
+
+    /// Checks `value` against the subset of JSON Schema the tool schemas use.
+    fn check_schema(value: &Value, schema: &Value, at: &str) -> Result<(), String> {
+        if let Some(options) = schema["anyOf"].as_array() {
+            return match options
+                .iter()
+                .any(|option| check_schema(value, option, at).is_ok())
+            {
+                true => Ok(()),
+                false => Err(format!("{at}: {value} matches no anyOf option")),
+            };
+        }
+        let type_matches = match schema["type"].as_str() {
+            Some("object") => value.is_object(),
+            Some("array") => value.is_array(),
+            Some("string") => value.is_string(),
+            Some("boolean") => value.is_boolean(),
+            Some("integer") => value.is_i64() || value.is_u64(),
+            Some("number") => value.is_number(),
+            _ => true,
+        };
+        if !type_matches {
+            return Err(format!("{at}: {value} is not a {}", schema["type"]));
+        }
+        if let Some(allowed) = schema["enum"].as_array()
+            && !allowed.contains(value)
+        {
+            return Err(format!("{at}: {value} is not in {allowed:?}"));
+        }
+        if let Some(items) = value.as_array() {
+            for (index, item) in items.iter().enumerate() {
+                check_schema(item, &schema["items"], &format!("{at}[{index}]"))?;
+            }
+        }
+        // Objects without `properties`, like the dry-run plan, are free-form.
+        let (Some(fields), Some(properties)) = (value.as_object(), schema.get("properties")) else {
+            return Ok(());
+        };
+        for required in schema["required"].as_array().into_iter().flatten() {
+            let required = required.as_str().unwrap();
+            if !fields.contains_key(required) {
+                return Err(format!("{at}: missing {required}"));
+            }
+        }
+        for (key, field) in fields {
+            let at = format!("{at}.{key}");
+            match properties.get(key) {
+                Some(property) => check_schema(field, property, &at)?,
+                None if schema["additionalProperties"].is_null() => {
+                    return Err(format!("{at} is not in the schema"));
+                }
+                None => check_schema(field, &schema["additionalProperties"], &at)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn output_schema(tool: &str) -> Value {
+        let request = "{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"tools/list\"}\n";
+        let mut responses = serve_input(request);
+        let tools = responses.remove(0)["result"]["tools"].take();
+        let tool = tools
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|entry| entry["name"] == tool);
+        tool.unwrap()["outputSchema"].clone()
+    }
+
+    #[test]
+    fn results_match_the_advertised_output_schema() {
+        let schema = output_schema("notify");
+        let arguments = |message: String| {
+            serde_json::json!({
+                "title": "Done", "message": message, "icon": "happy", "sound": "default",
+                "verbose": true
+            })
+        };
+        let sent = call_tool_with(true, "notify", arguments("Build passed".to_string()));
+        check_schema(&sent["structuredContent"], &schema, "notify").unwrap();
+        let failed = call_tool_with(true, "notify", arguments("x".repeat(10_000)));
+        check_schema(&failed["structuredContent"], &schema, "notify").unwrap();
+        let wrong = serde_json::json!({"sent": "yes", "warnings": []});
+        assert!(check_schema(&wrong, &schema, "notify").is_err());
+
+        let schema = output_schema("list_assets");
+        for detailed in [false, true] {
+            let listed = call_tool("list_assets", serde_json::json!({"detailed": detailed}));
+            check_schema(&listed["structuredContent"], &schema, "list_assets").unwrap();
+        }
+    }
+
+    /// A stdout whose reader has gone away, failing every write with `kind`.
+    struct ClosedPipe {
+        kind: io::ErrorKind,
+        writes: usize,
+    }
+
+    impl Write for ClosedPipe {
+        fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+            self.writes += 1;
+            Err(io::Error::from(self.kind))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn closed_stdout_ends_the_session_cleanly() {
+        let _logger = log::tests::lock_logger();
+        let input = "{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"ping\"}\n\
+                     {\"jsonrpc\":\"2.0\",\"id\":2,\"method\":\"ping\"}\n";
+        for kind in [io::ErrorKind::BrokenPipe, io::ErrorKind::WriteZero] {
+            let mut pipe = ClosedPipe { kind, writes: 0 };
+            serve(Config::default(), None, false, input.as_bytes(), &mut pipe).unwrap();
+            assert_eq!(pipe.writes, 1, "{kind:?}");
+        }
+
+        let mut pipe = ClosedPipe {
+            kind: io::ErrorKind::PermissionDenied,
+            writes: 0,
+        };
+        assert!(serve(Config::default(), None, false, input.as_bytes(), &mut pipe).is_err());
+    }
+
+    #[test]
+    fn notify_description_counts_the_available_assets() {
+        let fixtures =
+            ["asset-count-a", "asset-count-b"].map(crate::assets::tests::SoundFixture::new);
+        let config = Config {
+            allowed_icons: Some(vec!["happy".to_string(), "error".to_string()]),
+            allowed_sounds: Some(vec!["asset-count-*".to_string()]),
+            ..Config::default()
+        };
+        let tools = tool_descriptions(&config);
+        let notify = tools.iter().find(|tool| tool.name == "notify").unwrap();
+        assert!(
+            notify
+                .description
+                .starts_with("Send a system toast + sound.")
+        );
+        assert!(
+            notify
+                .description
+                .ends_with("(2 icons, 2 sounds available; call list_assets for ids)"),
+            "{}",
+            notify.description
+        );
+
+        let third = crate::assets::tests::SoundFixture::new("asset-count-c");
+        let tools = tool_descriptions(&config);
+        let notify = tools.iter().find(|tool| tool.name == "notify").unwrap();
+        assert!(notify.description.contains("(2 icons, 3 sounds available;"));
+        drop((fixtures, third));
+    }
+
+    #[test]
+    fn both_resource_template_spellings_get_the_same_answer() {
+        let input: String = [
+            "resources/templates/list",
+            "resource-templates/list",
+            "resources/list",
+        ]
+        .iter()
+        .map(|method| format!("{{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"{method}\"}}\n"))
+        .collect();
+        let responses = serve_input(&input);
+        assert_eq!(responses.len(), 3);
+        assert_eq!(responses[0], responses[1]);
+        let templates = responses[0]["result"]["resourceTemplates"]
+            .as_array()
+            .unwrap();
+        assert_eq!(templates.len(), 1);
+        assert_eq!(
+            templates[0]["uriTemplate"],
+            format!("{SOUND_DATA_URI_PREFIX}{{id}}")
+        );
+        assert!(responses[2]["result"].get("resourceTemplates").is_none());
+    }
+
+    #[test]
+    fn sound_data_serves_ids_with_spaces() {
+        let fixture = crate::assets::tests::SoundFixture::new("Level Up");
+        let read = |uri: &str| {
+            let request = serde_json::json!({
+                "jsonrpc": "2.0", "id": 1, "method": "resources/read", "params": {"uri": uri}
+            });
+            serve_input(&format!("{request}\n")).remove(0)
+        };
+        let expected =
+            base64::engine::general_purpose::STANDARD.encode(std::fs::read(&fixture.0).unwrap());
+        for uri in [
+            "toastmcp://sound-data/Level Up",
+            "toastmcp://sound-data/Level%20Up",
+        ] {
+            let response = read(uri);
+            assert_eq!(response["result"]["contents"][0]["blob"], expected, "{uri}");
+        }
+        let response = read("toastmcp://sound-data/..%2Fconfig");
+        assert_eq!(response["error"]["code"], -32602);
+        assert!(
+            response["error"]["message"]
+                .as_str()
+                .unwrap()
+                .contains("Invalid asset id")
+        );
+        drop(fixture);
+
+        assert_eq!(percent_decode("caf%C3%A9%2"), "caf\u{e9}%2");
+    }
+
+    #[test]
+    fn allowlists_filter_the_schema_and_reject_other_assets() {
+        let config = Config {
+            allowed_icons: Some(vec!["h*".to_string()]),
+            allowed_sounds: Some(vec!["default".to_string(), "mail".to_string()]),
+            ..Config::default()
+        };
+        let tools = tool_descriptions(&config);
+        let notify_tool = tools.iter().find(|tool| tool.name == "notify").unwrap();
+        let properties = &notify_tool.input_schema["properties"];
+        let icons = properties["icon"]["enum"].as_array().unwrap();
+        assert!(!icons.is_empty());
+        assert!(
+            icons
+                .iter()
+                .all(|icon| icon.as_str().unwrap().starts_with('h'))
+        );
+
+        let request = serde_json::json!({
+            "jsonrpc": "2.0", "id": 1, "method": "tools/call",
+            "params": {"name": "notify", "arguments": {
+                "title": "T", "message": "M", "icon": "error", "sound": "default"
+            }}
+        });
+        let responses = serve_with(config, &format!("{request}\n"));
+        let result = &responses[0]["result"];
+        assert_eq!(result["isError"], true);
+        let text = result["content"][0]["text"].as_str().unwrap();
+        assert!(text.contains("Icon error is not allowed"), "{text}");
+    }
+
+    #[test]
+    fn denied_assets_are_hidden_and_rejected() {
+        let config = Config {
+            denied_icons: vec!["happy".to_string()],
+            ..Config::default()
+        };
+        let result = call_tool("list_assets", serde_json::json!({}));
+        assert!(
+            result["structuredContent"]["icons"]
+                .as_array()
+                .unwrap()
+                .contains(&Value::from("happy"))
+        );
+        assert!(!list_icon_ids(&config).contains(&"happy".to_string()));
+
+        let request = serde_json::json!({
+            "jsonrpc": "2.0", "id": 1, "method": "tools/call",
+            "params": {"name": "notify", "arguments": {
+                "title": "T", "message": "M", "icon": "happy", "sound": "default"
+            }}
+        });
+        let responses = serve_with(config, &format!("{request}\n"));
+        let text = responses[0]["result"]["content"][0]["text"]
+            .as_str()
+            .unwrap();
+        assert!(text.contains("Icon happy is not allowed"), "{text}");
+    }
+}
@@ -1,379 +1,4019 @@
+use std::collections::{BTreeMap, HashMap};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
 use anyhow::{Context, Result};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
-use crate::assets::{list_icon_ids, list_sound_ids};
+use crate::assets::{
+    asset_dirs, bootstrap_default_icons, embedded_asset_path, find_asset_file, list_icon_ids,
+    list_sound_ids, list_sound_paths, nfc, planned_default_icon, write_atomic, WINDOWS_SOUND_IDS,
+};
+use crate::config::{glob_match, AudioMode, Config};
+use crate::error::NotifyError;
+use crate::log::{self, LogLevel};
+use crate::wav::{self, WavInfo};
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct NotifyInput {
     pub title: String,
+    /// Optional when `summary`/`detail` are used instead.
+    #[serde(default)]
     pub message: String,
+    /// Short banner line; `detail` follows it in the expanded Action Center entry.
+    #[serde(default)]
+    pub summary: Option<String>,
+    #[serde(default)]
+    pub detail: Option<String>,
+    /// Optional when `beep_sequence` is given instead.
+    #[serde(default)]
     pub sound: String,
     pub icon: String,
+    #[serde(default)]
+    pub mode: NotifyMode,
+    #[serde(default)]
+    pub sound_kind: SoundKind,
+    #[serde(default)]
+    pub strip_markdown: bool,
+    /// Seconds until Windows removes the toast, including its Action Center entry.
+    #[serde(default)]
+    pub auto_remove_secs: Option<u32>,
+    /// Key-value pairs passed back in the activation arguments when the toast is clicked.
+    #[serde(default)]
+    pub data: Option<BTreeMap<String, String>>,
+    /// `#RRGGBB` plate composited behind the icon, for transparent PNGs.
+    #[serde(default)]
+    pub icon_bg: Option<String>,
+    /// Conversation this toast belongs to; see `ThreadInput`.
+    #[serde(default)]
+    pub thread: Option<ThreadInput>,
+    /// Replaces an earlier toast with the same tag, and names it for `update_progress`.
+    #[serde(default)]
+    pub tag: Option<String>,
+    #[serde(default)]
+    pub progress: Option<ProgressInput>,
+    /// `hint-maxLines` for each body line, so a long line wraps to at most this many lines.
+    #[serde(default)]
+    pub body_max_lines: Option<u32>,
+    /// `hint-toastId`: a new toast with the same id collapses into this one in the Action Center.
+    #[serde(default)]
+    pub toast_id: Option<String>,
+    /// Loop a WAV sound, e.g. for alarms, until `audio_max_secs` pass or another sound plays.
+    #[serde(default)]
+    pub audio_loop: bool,
+    #[serde(default)]
+    pub audio_max_secs: Option<u32>,
+    /// Stamp the toast with its send time, which the Action Center shows as "x minutes ago".
+    #[serde(default)]
+    pub show_relative_time: bool,
+    /// Re-render the volume-scaled WAV instead of reusing the cached copy.
+    #[serde(default)]
+    pub no_cache: bool,
+    /// Tones played instead of `sound`, for cues that don't need a WAV.
+    #[serde(default)]
+    pub beep_sequence: Option<Vec<BeepTone>>,
+    /// Report how long each delivery phase took in the result.
+    #[serde(default)]
+    pub verbose: bool,
+    /// Caller's tracing id from `tools/call` `_meta`, attached to log lines for this toast.
+    #[serde(skip)]
+    pub correlation_id: Option<String>,
+    /// Validate and resolve everything, then report a `NotifyPlan` instead of delivering.
+    #[serde(skip)]
+    pub dry_run: bool,
 }
 
-pub fn notify(input: NotifyInput) -> Result<()> {
-    let icon_path = match resolve_icon(&input.icon) {
+/// Related notifications from a chat-like agent. Toasts sharing `id` get the same
+/// toast group, and `title` is shown as the attribution line on each of them.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ThreadInput {
+    pub id: String,
+    pub title: String,
+}
+
+/// One tone of a `beep_sequence`.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[cfg_attr(not(windows), allow(dead_code))]
+pub struct BeepTone {
+    /// Frequency in hertz.
+    pub freq: u32,
+    pub ms: u32,
+}
+
+/// A progress bar whose value and status are data-bound, so `update_progress` can
+/// change them in place without re-showing the toast.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(not(windows), allow(dead_code))]
+pub struct ProgressInput {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<ProgressValue>,
+    /// A whole percent, as an alternative to a fractional `value`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub percent: Option<u64>,
+    #[serde(default)]
+    pub status: String,
+}
+
+impl ProgressInput {
+    pub fn amount(&self) -> Result<ProgressValue> {
+        progress_amount(self.value.as_ref(), self.percent)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[cfg_attr(not(windows), allow(dead_code))]
+pub struct UpdateProgressInput {
+    pub tag: String,
+    /// The thread id, if the toast was sent with one.
+    #[serde(default)]
+    pub group: Option<String>,
+    #[serde(default)]
+    pub value: Option<ProgressValue>,
+    #[serde(default)]
+    pub percent: Option<u64>,
+    /// Left unchanged when omitted.
+    #[serde(default)]
+    pub status: Option<String>,
+}
+
+impl UpdateProgressInput {
+    pub fn amount(&self) -> Result<ProgressValue> {
+        progress_amount(self.value.as_ref(), self.percent)
+    }
+}
+
+/// Completion of a progress bar: a fraction (`0.5`, where `1` is a full bar), or
+/// `"indeterminate"` for a spinner-style bar. Whole percents come in separately as
+/// `percent`, so a number never means two different things.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum ProgressValue {
+    Fraction(f64),
+    Text(String),
+}
+
+impl ProgressValue {
+    /// The `<progress value>` binding, which only takes a 0.0-1.0 fraction or `indeterminate`.
+    #[cfg_attr(not(windows), allow(dead_code))]
+    fn binding(&self) -> String {
+        match self {
+            ProgressValue::Fraction(fraction) => fraction.to_string(),
+            ProgressValue::Text(_) => "indeterminate".to_string(),
+        }
+    }
+}
+
+/// Windows rejects toast tags and groups longer than this.
+const MAX_GROUP_CHARS: usize = 64;
+
+/// How long `audio_loop` plays when `audio_max_secs` isn't given, and the longest it may play.
+const DEFAULT_LOOP_SECS: u32 = 15;
+const MAX_LOOP_SECS: u32 = 300;
+
+/// `Beep` only accepts frequencies in this range.
+const MIN_BEEP_FREQ: u32 = 37;
+const MAX_BEEP_FREQ: u32 = 32_767;
+
+/// A beep sequence is a short cue, not a tune.
+const MAX_BEEP_TONES: usize = 16;
+const MAX_BEEP_TONE_MS: u32 = 2_000;
+const MAX_BEEP_TOTAL_MS: u32 = 5_000;
+
+/// Windows drops toasts from the Action Center after three days anyway.
+const MAX_AUTO_REMOVE_SECS: u32 = 3 * 24 * 60 * 60;
+
+/// The last file handed to `play_sound`; cache cleanup leaves it alone in case it is still playing.
+static LAST_PLAYED: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// Keeps the `launch` attribute well inside `MAX_TOAST_PAYLOAD_BYTES`.
+const MAX_LAUNCH_BYTES: usize = 2048;
+
+/// Windows documents a 5 KB limit on a toast's XML payload; past it, `Show` fails with
+/// an opaque WinRT error, so `notify` rejects such toasts before delivery.
+const MAX_TOAST_PAYLOAD_BYTES: usize = 5 * 1024;
+
+/// Upper bound on the toast XML around the caller's text: element names, the optional
+/// attributes, progress, image and audio fragments.
+const TOAST_XML_OVERHEAD_BYTES: usize = 512;
+/// Upper bound on the markup around one body line, `hint-maxLines` included.
+const TOAST_XML_LINE_OVERHEAD_BYTES: usize = 64;
+
+/// Which kind of sound a `sound` id refers to when a WAV and a system sound share it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SoundKind {
+    /// WAV from `sounds/` first, then the Windows system sound.
+    #[default]
+    Auto,
+    File,
+    System,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotifyMode {
+    #[default]
+    Normal,
+    /// Silent Action Center entry: no banner and no sound.
+    LogOnly,
+}
+
+/// Everything needed to render and show a single toast.
+#[derive(Debug, Clone)]
+#[cfg_attr(not(windows), allow(dead_code))]
+struct ToastSpec {
+    title: String,
+    /// Body lines, each rendered as its own `<text>` element.
+    body: Vec<String>,
+    icon_path: Option<PathBuf>,
+    audio_src: Option<&'static str>,
+    suppress_popup: bool,
+    tag: Option<String>,
+    expires_in: Option<std::time::Duration>,
+    launch: Option<String>,
+    /// Initial `progressValue`/`progressStatus` for the bound `<progress>` element.
+    progress: Option<ProgressInput>,
+    group: Option<String>,
+    /// Small line under the body (`placement="attribution"`), used for thread titles.
+    attribution: Option<String>,
+    /// ISO 8601 UTC time for the `<toast displayTimestamp>` attribute.
+    display_timestamp: Option<String>,
+    /// `hint-maxLines` on each body `<text>` element.
+    body_max_lines: Option<u32>,
+    /// Value for the `<toast hint-toastId>` attribute.
+    toast_id: Option<String>,
+    correlation_id: Option<String>,
+    /// Parse `build_toast_xml` even when the cached template would fit.
+    load_xml: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct BenchmarkInput {
+    #[serde(default = "default_benchmark_count")]
+    pub count: u32,
+    #[serde(default = "default_benchmark_tag")]
+    pub tag: String,
+    /// Parse the full toast XML every time instead of filling the cached template,
+    /// to compare the two paths.
+    #[serde(default)]
+    pub load_xml: bool,
+}
+
+fn default_benchmark_count() -> u32 {
+    10
+}
+
+fn default_benchmark_tag() -> String {
+    "toastmcp-benchmark".to_string()
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchmarkReport {
+    pub count: u32,
+    pub min_ms: f64,
+    pub avg_ms: f64,
+    pub max_ms: f64,
+    pub p95_ms: f64,
+}
+
+/// What a successful `notify` reports back besides "sent".
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct NotifyOutcome {
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+    /// The sound picked when `sound` was a glob such as `chime*`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sound: Option<String>,
+    /// Per-phase durations, when the call asked for `verbose`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timings: Option<NotifyTimings>,
+    /// What would have been shown and played, for a dry run.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub plan: Option<NotifyPlan>,
+}
+
+/// Result of `notify_confirmed`: the usual outcome plus what the Action Center history showed.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfirmedNotification {
+    pub shown: bool,
+    pub tag: String,
+    /// Toasts with this tag (and thread) in the history; `None` when it couldn't be read.
+    pub history_count: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub history_error: Option<String>,
+    #[serde(flatten)]
+    pub outcome: NotifyOutcome,
+}
+
+/// Numbers the tags `notify_confirmed` makes up for calls without one.
+static CONFIRM_TAG_COUNTER: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+
+/// The toast and sound a dry-run `notify` resolved to.
+#[derive(Debug, Clone, Serialize)]
+pub struct NotifyPlan {
+    pub title: String,
+    pub body: Vec<String>,
+    pub icon: Option<PathBuf>,
+    pub sound: PlannedSound,
+    pub mode: NotifyMode,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attribution: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub display_timestamp: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub toast_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body_max_lines: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub progress: Option<ProgressInput>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_in_secs: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub launch: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub loop_secs: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PlannedSound {
+    /// `log_only` toasts are silent.
+    None,
+    File { path: PathBuf },
+    System { id: String },
+    BeepSequence { tones: usize, total_ms: u32 },
+}
+
+/// Milliseconds spent in each phase of a `notify`. Phases that didn't run stay 0.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct NotifyTimings {
+    /// Argument checks and icon/sound lookup, up to delivery.
+    pub validate_ms: f64,
+    /// Volume-scaling the WAV (a cache hit is near zero).
+    pub prepare_sound_ms: f64,
+    pub play_sound_ms: f64,
+    pub com_init_ms: f64,
+    /// App id registration and the Start Menu shortcut check or rewrite.
+    pub shortcut_ms: f64,
+    /// Building the toast XML and `Show`.
+    pub show_ms: f64,
+    pub total_ms: f64,
+}
+
+fn elapsed_ms(started: Instant) -> f64 {
+    started.elapsed().as_secs_f64() * 1000.0
+}
+
+/// ToastGeneric shows at most three `<text>` elements, and the title takes one.
+const MAX_BODY_LINES: usize = 2;
+
+/// ToastGeneric wraps the title and body to four lines in total, so no text element can use more.
+const MAX_TEXT_LINES: u32 = 4;
+
+/// Summaries longer than this are cut so the banner stays a one-glance read.
+const MAX_SUMMARY_CHARS: usize = 100;
+
+pub fn notify(input: NotifyInput, config: &Config) -> Result<NotifyOutcome> {
+    notify_with(input, config, &show_toast_timed)
+}
+
+/// Shows a built toast: `show_toast_timed`, or a stand-in in tests.
+type ShowToast<'a> = &'a dyn Fn(&ToastSpec, &Config, &mut NotifyTimings) -> Result<()>;
+
+fn notify_with(mut input: NotifyInput, config: &Config, show: ShowToast) -> Result<NotifyOutcome> {
+    // A dry run resolves everything a real one would, but must not write any file.
+    let read_only_config;
+    let config = if input.dry_run && !config.read_only {
+        read_only_config = read_only(config);
+        &read_only_config
+    } else {
+        config
+    };
+    let started = Instant::now();
+    let mut timings = NotifyTimings::default();
+    input.icon = nfc(&input.icon);
+    input.sound = nfc(&input.sound);
+    if let Some(secs) = input.auto_remove_secs
+        && !(1..=MAX_AUTO_REMOVE_SECS).contains(&secs)
+    {
+        return Err(anyhow::anyhow!(
+            "auto_remove_secs must be between 1 and {MAX_AUTO_REMOVE_SECS} (got {secs})"
+        ));
+    }
+    let uses_detail = input.summary.is_some() || input.detail.is_some();
+    if uses_detail && !input.message.is_empty() {
+        return Err(anyhow::anyhow!(
+            "Use either message or summary/detail, not both"
+        ));
+    }
+    if let Some(thread) = &input.thread
+        && (thread.id.is_empty() || thread.id.chars().count() > MAX_GROUP_CHARS)
+    {
+        return Err(anyhow::anyhow!(
+            "thread.id must be 1 to {MAX_GROUP_CHARS} characters (got {:?})",
+            thread.id
+        ));
+    }
+    if let Some(tag) = &input.tag
+        && (tag.is_empty() || tag.chars().count() > MAX_GROUP_CHARS)
+    {
+        return Err(anyhow::anyhow!(
+            "tag must be 1 to {MAX_GROUP_CHARS} characters (got {tag:?})"
+        ));
+    }
+    if let Some(toast_id) = &input.toast_id
+        && (toast_id.is_empty() || toast_id.chars().count() > MAX_GROUP_CHARS)
+    {
+        return Err(anyhow::anyhow!(
+            "toast_id must be 1 to {MAX_GROUP_CHARS} characters (got {toast_id:?})"
+        ));
+    }
+    if let Some(progress) = &input.progress {
+        progress.amount()?;
+        if input.tag.is_none() {
+            return Err(anyhow::anyhow!(
+                "progress needs a tag so update_progress can find the toast"
+            ));
+        }
+    }
+    let loop_for = match (input.audio_loop, input.audio_max_secs) {
+        (false, None) => None,
+        (false, Some(_)) => {
+            return Err(anyhow::anyhow!("audio_max_secs only applies with audio_loop"));
+        }
+        (true, secs) => {
+            let secs = secs.unwrap_or(DEFAULT_LOOP_SECS);
+            if !(1..=MAX_LOOP_SECS).contains(&secs) {
+                return Err(anyhow::anyhow!(
+                    "audio_max_secs must be between 1 and {MAX_LOOP_SECS} (got {secs})"
+                ));
+            }
+            Some(std::time::Duration::from_secs(secs.into()))
+        }
+    };
+    if loop_for.is_some() && input.mode == NotifyMode::LogOnly {
+        return Err(anyhow::anyhow!(
+            "audio_loop doesn't apply to log_only toasts, which are silent"
+        ));
+    }
+    if let Some(tones) = &input.beep_sequence {
+        validate_beep_sequence(tones)?;
+        if input.audio_loop {
+            return Err(anyhow::anyhow!("audio_loop doesn't apply to beep_sequence"));
+        }
+        if input.mode == NotifyMode::LogOnly {
+            return Err(anyhow::anyhow!(
+                "beep_sequence doesn't apply to log_only toasts, which are silent"
+            ));
+        }
+    } else if input.sound.is_empty() {
+        return Err(anyhow::anyhow!(
+            "sound is required unless beep_sequence is given"
+        ));
+    }
+    let launch = input.data.as_ref().map(encode_launch_data);
+    if let Some(launch) = launch.as_deref()
+        && launch.len() > MAX_LAUNCH_BYTES
+    {
+        return Err(anyhow::anyhow!(
+            "data is too large: {} bytes once encoded (limit {MAX_LAUNCH_BYTES})",
+            launch.len()
+        ));
+    }
+
+    let uses_sound = input.beep_sequence.is_none();
+    let picked_sound = if uses_sound && is_sound_set(&input.sound) {
+        let sound = pick_random_sound(&input.sound, config)?;
+        input.sound = sound.clone();
+        Some(sound)
+    } else {
+        None
+    };
+    if !config.allows_icon(&input.icon) {
+        return Err(anyhow::anyhow!(
+            "Icon {} is not allowed by this server's config. Allowed icon ids: {}.",
+            input.icon,
+            format_ids(&list_icon_ids(config))
+        ));
+    }
+    if uses_sound && !config.allows_sound(&input.sound) {
+        return Err(anyhow::anyhow!(
+            "Sound {} is not allowed by this server's config. Allowed sound ids: {}.",
+            input.sound,
+            format_ids(&list_sound_ids(config))
+        ));
+    }
+
+    if loop_for.is_some()
+        && let PlannedSound::System { id } =
+            plan_sound(&input.sound, input.sound_kind, input.mode, None, config)?
+    {
+        return Err(anyhow::anyhow!(
+            "audio_loop needs a WAV file, but {id} plays as a Windows system sound"
+        ));
+    }
+
+    let icon_path = match resolve_icon(&input.icon, config) {
         Ok(path) => path,
         Err(_) => {
-            let icon_ids = list_icon_ids();
+            let icon_ids = list_icon_ids(config);
             return Err(anyhow::anyhow!(
                 "Missing asset: icons/{}.png. Valid icon ids: {}. Use tools/list to refresh.",
                 input.icon,
                 format_ids(&icon_ids)
             ));
         }
-    };
-    if let Some(sound_path) = find_sound_path(&input.sound) {
-        let playback_path = prepare_quiet_wav(&sound_path, 0.7).unwrap_or(sound_path);
-        play_sound(&playback_path)?;
-        show_toast(&input.title, &input.message, Some(icon_path.as_path()), None)?;
-        return Ok(());
+    };
+    let mut warnings = Vec::new();
+    let body_max_lines = input.body_max_lines.map(|lines| {
+        let clamped = lines.clamp(1, MAX_TEXT_LINES);
+        if clamped != lines {
+            warnings.push(format!(
+                "body_max_lines must be 1 to {MAX_TEXT_LINES}; used {clamped} instead of {lines}."
+            ));
+        }
+        clamped
+    });
+    let icon_path = match input.icon_bg.as_deref() {
+        Some(color) => {
+            let rgb = parse_hex_color(color)?;
+            match icon_with_background(&icon_path, rgb, config.read_only) {
+                Ok(path) => path,
+                Err(err) => {
+                    warnings.push(format!("icon_bg was ignored: {err:#}"));
+                    icon_path
+                }
+            }
+        }
+        None => icon_path,
+    };
+    let strip_markdown_enabled = input.strip_markdown;
+    let clean = |text: String| {
+        if strip_markdown_enabled {
+            strip_markdown(&text)
+        } else {
+            text
+        }
+    };
+    let body = if uses_detail {
+        summary_detail_lines(input.summary.map(clean), input.detail.map(clean))
+    } else {
+        split_body_lines(&clean(input.message), &mut warnings)
+    };
+    let mut spec = ToastSpec {
+        title: input.title,
+        body,
+        icon_path: Some(icon_path),
+        audio_src: None,
+        suppress_popup: false,
+        tag: input.tag,
+        expires_in: input
+            .auto_remove_secs
+            .map(|secs| std::time::Duration::from_secs(secs.into())),
+        launch,
+        progress: input.progress,
+        group: input.thread.as_ref().map(|thread| thread.id.clone()),
+        attribution: input.thread.map(|thread| thread.title),
+        display_timestamp: input
+            .show_relative_time
+            .then(|| iso8601_utc(std::time::SystemTime::now())),
+        body_max_lines,
+        toast_id: input.toast_id,
+        correlation_id: input.correlation_id,
+        load_xml: false,
+    };
+    let bytes = toast_xml_len(&spec);
+    if bytes > MAX_TOAST_PAYLOAD_BYTES {
+        return Err(NotifyError::ToastTooLarge {
+            bytes,
+            limit: MAX_TOAST_PAYLOAD_BYTES,
+        }
+        .into());
+    }
+    timings.validate_ms = elapsed_ms(started);
+
+    if input.dry_run {
+        let sound = plan_sound(
+            &input.sound,
+            input.sound_kind,
+            input.mode,
+            input.beep_sequence.as_deref(),
+            config,
+        )?;
+        return Ok(NotifyOutcome {
+            warnings,
+            sound: picked_sound,
+            timings: None,
+            plan: Some(NotifyPlan {
+                title: spec.title,
+                body: spec.body,
+                icon: spec.icon_path,
+                sound,
+                mode: input.mode,
+                tag: spec.tag,
+                group: spec.group,
+                attribution: spec.attribution,
+                display_timestamp: spec.display_timestamp,
+                toast_id: spec.toast_id,
+                body_max_lines: spec.body_max_lines,
+                progress: spec.progress,
+                expires_in_secs: spec.expires_in.map(|expires_in| expires_in.as_secs()),
+                launch: spec.launch,
+                loop_secs: loop_for.map(|duration| duration.as_secs()),
+            }),
+        });
+    }
+
+    begin_sound();
+    let delivered = match input.beep_sequence.as_deref() {
+        _ if input.mode == NotifyMode::LogOnly => {
+            spec.suppress_popup = true;
+            show(&spec, config, &mut timings)
+        }
+        Some(tones) => deliver_beeps(&spec, tones, config, show, &mut timings),
+        None => deliver(
+            &mut spec,
+            &input.sound,
+            input.sound_kind,
+            Playback {
+                loop_for,
+                no_cache: input.no_cache,
+            },
+            config,
+            show,
+            &mut timings,
+        ),
+    };
+    timings.total_ms = elapsed_ms(started);
+    log_timings(&timings, spec.correlation_id.as_deref());
+    if let Some(id) = spec.correlation_id.as_deref() {
+        match &delivered {
+            Ok(()) => log::write(
+                LogLevel::Info,
+                "notification sent",
+                serde_json::json!({"correlation_id": id}),
+            ),
+            Err(err) => log::write(
+                LogLevel::Warn,
+                "notification failed",
+                serde_json::json!({"correlation_id": id, "error": format!("{err:#}")}),
+            ),
+        }
+    }
+    delivered?;
+    if config.show_onboarding {
+        show_onboarding_once(config);
+    }
+    Ok(NotifyOutcome {
+        warnings,
+        sound: picked_sound,
+        timings: input.verbose.then_some(timings),
+        plan: None,
+    })
+}
+
+fn log_timings(timings: &NotifyTimings, correlation_id: Option<&str>) {
+    let mut fields = serde_json::to_value(timings).unwrap_or_default();
+    if let Some(correlation_id) = correlation_id {
+        fields["correlation_id"] = serde_json::Value::from(correlation_id);
+    }
+    log::write(LogLevel::Trace, "notify timings", fields);
+}
+
+/// Sends a notification, then looks its tag up in the Action Center history to
+/// confirm it landed. Calls without a tag get a generated one.
+pub fn notify_confirmed(mut input: NotifyInput, config: &Config) -> Result<ConfirmedNotification> {
+    let tag = input
+        .tag
+        .get_or_insert_with(|| {
+            let n = CONFIRM_TAG_COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            format!("toastmcp-confirm-{}-{n}", std::process::id())
+        })
+        .clone();
+    let group = input.thread.as_ref().map(|thread| thread.id.clone());
+    let outcome = notify(input, config)?;
+    if outcome.plan.is_some() {
+        return Ok(ConfirmedNotification {
+            shown: false,
+            tag,
+            history_count: None,
+            history_error: None,
+            outcome,
+        });
+    }
+
+    let (history_count, history_error) = match count_history(&tag, group.as_deref()) {
+        Ok(count) => (Some(count), None),
+        Err(err) => (None, Some(format!("{err:#}"))),
+    };
+    Ok(ConfirmedNotification {
+        shown: true,
+        tag,
+        history_count,
+        history_error,
+        outcome,
+    })
+}
+
+/// Formats `time` as `YYYY-MM-DDTHH:MM:SSZ`, the form `displayTimestamp` expects.
+fn iso8601_utc(time: std::time::SystemTime) -> String {
+    let secs = time
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+    let (days, secs_of_day) = (secs / 86_400, secs % 86_400);
+
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm).
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        secs_of_day / 3_600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60
+    )
+}
+
+/// A `sound` containing `*` or `?` names a set of sounds to pick from at random.
+fn is_sound_set(sound: &str) -> bool {
+    sound.contains(['*', '?'])
+}
+
+/// A set without a `/` matches ids; one with a `/` (e.g. `chimes/*`) matches each
+/// sound's path under `sounds/`, so a subfolder can be a set.
+fn pick_random_sound(pattern: &str, config: &Config) -> Result<String> {
+    use std::hash::{BuildHasher, RandomState};
+
+    let pattern = pattern.replace('\\', "/");
+    let matches: Vec<String> = if pattern.contains('/') {
+        list_sound_paths(config)
+            .into_iter()
+            .filter(|(_, path)| glob_match(&pattern, path))
+            .map(|(id, _)| id)
+            .collect()
+    } else {
+        list_sound_ids(config)
+            .into_iter()
+            .filter(|id| glob_match(&pattern, id))
+            .collect()
+    };
+    if matches.is_empty() {
+        return Err(anyhow::anyhow!(
+            "No sounds match {pattern}. Valid sound ids: {}. Use tools/list to refresh.",
+            format_ids(&list_sound_ids(config))
+        ));
+    }
+    // RandomState is seeded per instance, which is plenty for picking a sound.
+    let index = RandomState::new().hash_one(pattern) as usize % matches.len();
+    Ok(matches[index].clone())
+}
+
+/// Shows the welcome toast on a background thread the first time a notification
+/// succeeds. The marker file is claimed before showing, so it never fires twice.
+fn show_onboarding_once(config: &Config) {
+    let Ok(marker) = onboarding_marker_path() else {
+        return;
+    };
+    if std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&marker)
+        .is_err()
+    {
+        return;
+    }
+
+    let config = config.clone();
+    std::thread::spawn(move || {
+        let spec = ToastSpec {
+            title: format!("{} is set up", config.display_name),
+            body: vec![
+                "Notifications from your agents will appear here.".to_string(),
+                "Change the name, volume and assets in toastmcp.toml.".to_string(),
+            ],
+            icon_path: None,
+            audio_src: None,
+            suppress_popup: false,
+            tag: Some("toastmcp-onboarding".to_string()),
+            expires_in: None,
+            launch: None,
+            progress: None,
+            group: None,
+            attribution: None,
+            display_timestamp: None,
+            body_max_lines: None,
+            toast_id: None,
+            correlation_id: None,
+            load_xml: false,
+        };
+        if let Err(err) = show_toast(&spec, &config) {
+            eprintln!("toastmcp: onboarding toast failed: {err:#}");
+        }
+    });
+}
+
+/// Lives next to the exe rather than in `cache/`, which `cleanup_on_exit` empties.
+fn onboarding_marker_path() -> Result<PathBuf> {
+    let exe_dir = std::env::current_exe()
+        .ok()
+        .and_then(|p| p.parent().map(Path::to_path_buf))
+        .context("Failed to resolve exe directory")?;
+    Ok(exe_dir.join(".onboarded"))
+}
+
+/// How a WAV sound is played. Both fields only apply to WAV sounds; toast system
+/// sounds can't loop outside alarm scenarios and aren't cached.
+#[derive(Debug, Clone, Copy)]
+struct Playback {
+    loop_for: Option<std::time::Duration>,
+    no_cache: bool,
+}
+
+/// Picks the sound for `spec`, plays it, and shows the toast. `log_only` toasts
+/// never get here, since they are silent.
+fn deliver(
+    spec: &mut ToastSpec,
+    sound: &str,
+    sound_kind: SoundKind,
+    playback: Playback,
+    config: &Config,
+    show: ShowToast,
+    timings: &mut NotifyTimings,
+) -> Result<()> {
+    let mut decode_error = None;
+    if sound_kind != SoundKind::System
+        && let Some(sound_path) = find_sound_path(sound, config)
+    {
+        match check_playable_wav(sound, &sound_path) {
+            Ok(()) => {
+                let phase = Instant::now();
+                let playback_path =
+                    prepare_quiet_wav(&sound_path, config.volume as f32, playback.no_cache, false)
+                        .map(|prepared| prepared.path)
+                        .unwrap_or(sound_path);
+                timings.prepare_sound_ms = elapsed_ms(phase);
+                let phase = Instant::now();
+                play_sound(&playback_path, playback.loop_for, config)?;
+                timings.play_sound_ms = elapsed_ms(phase);
+                *LAST_PLAYED
+                    .lock()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(playback_path);
+                if config.audio_mode == AudioMode::Both {
+                    spec.audio_src = system_sound_to_audio_src(sound)
+                        .or_else(|| system_sound_to_audio_src("default"));
+                }
+                return show(spec, config, timings);
+            }
+            Err(err) => decode_error = Some(err),
+        }
+    }
+
+    if sound_kind != SoundKind::File
+        && let Some(audio_src) = system_sound_to_audio_src(sound)
+    {
+        spec.audio_src = Some(audio_src);
+        return show(spec, config, timings);
+    }
+
+    if let Some(err) = decode_error {
+        return Err(err.into());
+    }
+    Err(sound_not_found(sound, sound_kind, config))
+}
+
+/// Resolves the sound `deliver` would use, with the same precedence and errors,
+/// without playing anything.
+fn plan_sound(
+    sound: &str,
+    sound_kind: SoundKind,
+    mode: NotifyMode,
+    beep_sequence: Option<&[BeepTone]>,
+    config: &Config,
+) -> Result<PlannedSound> {
+    if mode == NotifyMode::LogOnly {
+        return Ok(PlannedSound::None);
+    }
+    if let Some(tones) = beep_sequence {
+        return Ok(PlannedSound::BeepSequence {
+            tones: tones.len(),
+            total_ms: beep_sequence_ms(tones),
+        });
+    }
+
+    let mut decode_error = None;
+    if sound_kind != SoundKind::System
+        && let Some(path) = find_sound_path(sound, config)
+    {
+        match check_playable_wav(sound, &path) {
+            Ok(()) => return Ok(PlannedSound::File { path }),
+            Err(err) => decode_error = Some(err),
+        }
+    }
+    if sound_kind != SoundKind::File && system_sound_to_audio_src(sound).is_some() {
+        return Ok(PlannedSound::System {
+            id: sound.to_string(),
+        });
+    }
+    if let Some(err) = decode_error {
+        return Err(err.into());
+    }
+    Err(sound_not_found(sound, sound_kind, config))
+}
+
+fn sound_not_found(sound: &str, sound_kind: SoundKind, config: &Config) -> anyhow::Error {
+    let sound_ids = match sound_kind {
+        SoundKind::System => WINDOWS_SOUND_IDS
+            .iter()
+            .filter(|id| config.allows_sound(id))
+            .map(|s| s.to_string())
+            .collect(),
+        SoundKind::Auto | SoundKind::File => list_sound_ids(config),
+    };
+    anyhow::anyhow!(
+        "Sound not found: {}. Valid sound ids: {}. Use tools/list to refresh.",
+        sound,
+        format_ids(&sound_ids)
+    )
+}
+
+/// Shows the toast silently and plays `tones` alongside it. `notify` has already
+/// rejected beeps on `log_only` toasts.
+fn deliver_beeps(
+    spec: &ToastSpec,
+    tones: &[BeepTone],
+    config: &Config,
+    show: ShowToast,
+    timings: &mut NotifyTimings,
+) -> Result<()> {
+    let phase = Instant::now();
+    play_beep_sequence(tones, config)?;
+    timings.play_sound_ms = elapsed_ms(phase);
+    show(spec, config, timings)
+}
+
+fn validate_beep_sequence(tones: &[BeepTone]) -> Result<()> {
+    if tones.is_empty() || tones.len() > MAX_BEEP_TONES {
+        return Err(anyhow::anyhow!(
+            "beep_sequence must have 1 to {MAX_BEEP_TONES} tones (got {})",
+            tones.len()
+        ));
+    }
+    for tone in tones {
+        if !(MIN_BEEP_FREQ..=MAX_BEEP_FREQ).contains(&tone.freq) {
+            return Err(anyhow::anyhow!(
+                "beep freq must be between {MIN_BEEP_FREQ} and {MAX_BEEP_FREQ} Hz (got {})",
+                tone.freq
+            ));
+        }
+        if !(1..=MAX_BEEP_TONE_MS).contains(&tone.ms) {
+            return Err(anyhow::anyhow!(
+                "beep ms must be between 1 and {MAX_BEEP_TONE_MS} (got {})",
+                tone.ms
+            ));
+        }
+    }
+    let total_ms = beep_sequence_ms(tones);
+    if total_ms > MAX_BEEP_TOTAL_MS {
+        return Err(anyhow::anyhow!(
+            "beep_sequence lasts {total_ms} ms; the limit is {MAX_BEEP_TOTAL_MS} ms"
+        ));
+    }
+    Ok(())
+}
+
+fn beep_sequence_ms(tones: &[BeepTone]) -> u32 {
+    tones
+        .iter()
+        .fold(0u32, |total, tone| total.saturating_add(tone.ms))
+}
+
+/// Splits the message on newlines, since a single `<text>` element collapses them.
+/// Lines past `MAX_BODY_LINES` are dropped with a warning.
+fn split_body_lines(message: &str, warnings: &mut Vec<String>) -> Vec<String> {
+    let mut lines: Vec<String> = message
+        .lines()
+        .map(str::trim_end)
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect();
+    if lines.is_empty() {
+        lines.push(String::new());
+    }
+    if lines.len() > MAX_BODY_LINES {
+        warnings.push(format!(
+            "Message has {} lines but toasts show at most {MAX_BODY_LINES}; the rest were dropped.",
+            lines.len()
+        ));
+        lines.truncate(MAX_BODY_LINES);
+    }
+    lines
+}
+
+/// Renders `summary` (truncated) then `detail` as one line each. Windows shows the same
+/// content in the banner and the Action Center, but clips long text in the banner, so the
+/// summary leads and the full detail is readable once the entry is expanded.
+fn summary_detail_lines(summary: Option<String>, detail: Option<String>) -> Vec<String> {
+    let one_line = |text: String| text.split_whitespace().collect::<Vec<_>>().join(" ");
+    let mut lines = Vec::new();
+    if let Some(summary) = summary.map(one_line).filter(|summary| !summary.is_empty()) {
+        lines.push(match summary.char_indices().nth(MAX_SUMMARY_CHARS) {
+            Some((end, _)) => format!("{}…", summary[..end].trim_end()),
+            None => summary,
+        });
+    }
+    if let Some(detail) = detail.map(one_line).filter(|detail| !detail.is_empty()) {
+        lines.push(detail);
+    }
+    if lines.is_empty() {
+        lines.push(String::new());
+    }
+    lines
+}
+
+/// Checks that exactly one of `value` and `percent` is set and in range, and turns
+/// either into the fraction (or `indeterminate`) the toast binds to.
+fn progress_amount(value: Option<&ProgressValue>, percent: Option<u64>) -> Result<ProgressValue> {
+    match (value, percent) {
+        (Some(_), Some(_)) => Err(anyhow::anyhow!(
+            "progress takes either value or percent, not both"
+        )),
+        (None, None) => Err(anyhow::anyhow!("progress needs a value or a percent")),
+        (None, Some(percent)) if percent > 100 => Err(anyhow::anyhow!(
+            "progress percent must be between 0 and 100 (got {percent})"
+        )),
+        (None, Some(percent)) => Ok(ProgressValue::Fraction(percent as f64 / 100.0)),
+        (Some(ProgressValue::Fraction(fraction)), None) if !(0.0..=1.0).contains(fraction) => {
+            Err(anyhow::anyhow!(
+                "progress value must be between 0.0 and 1.0 (got {fraction}); use percent for whole percents"
+            ))
+        }
+        (Some(ProgressValue::Text(text)), None) if text != "indeterminate" => Err(anyhow::anyhow!(
+            "progress value must be a number or \"indeterminate\" (got {text:?})"
+        )),
+        (Some(value), None) => Ok(value.clone()),
+    }
+}
+
+/// Pushes new progress values to a toast shown with `progress`, without re-showing it.
+/// Returns `false` when the update was coalesced: updates to the same tag within
+/// `progress_debounce_ms` of the last one pushed wait, and only the latest is pushed
+/// when the interval ends.
+pub fn update_progress(input: UpdateProgressInput, config: &Config) -> Result<bool> {
+    use std::sync::OnceLock;
+
+    static DEBOUNCER: OnceLock<Arc<ProgressDebouncer<(UpdateProgressInput, Config)>>> =
+        OnceLock::new();
+
+    input.amount()?;
+    let debouncer = DEBOUNCER.get_or_init(|| {
+        ProgressDebouncer::new(|(input, config): (UpdateProgressInput, Config)| {
+            push_progress_update(&input, &config).map_err(|err| {
+                log::write(
+                    LogLevel::Warn,
+                    "progress update failed",
+                    serde_json::json!({"tag": input.tag, "error": format!("{err:#}")}),
+                );
+                err
+            })
+        })
+    });
+    let key = format!("{}\u{0}{}", input.tag, input.group.as_deref().unwrap_or(""));
+    let interval = std::time::Duration::from_millis(config.progress_debounce_ms);
+    debouncer.submit(key, (input, config.clone()), interval)
+}
+
+/// Rate-limits pushes per key. The first value in an interval is pushed at once; later
+/// ones replace each other and the latest is pushed when the interval ends, so the
+/// final value always lands.
+struct ProgressDebouncer<T> {
+    push: Box<dyn Fn(T) -> Result<()> + Send + Sync>,
+    slots: Mutex<HashMap<String, DebounceSlot<T>>>,
+}
+
+struct DebounceSlot<T> {
+    last_pushed: Option<Instant>,
+    pending: Option<T>,
+    /// A flush thread is waiting to push `pending`.
+    flushing: bool,
+}
+
+impl<T: Send + 'static> ProgressDebouncer<T> {
+    fn new(push: impl Fn(T) -> Result<()> + Send + Sync + 'static) -> Arc<Self> {
+        Arc::new(Self {
+            push: Box::new(push),
+            slots: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Pushes `value` now and returns `true`, or holds it for the flush thread and
+    /// returns `false`. Only an immediate push can fail here; a deferred one is
+    /// reported by `push` itself.
+    fn submit(
+        self: &Arc<Self>,
+        key: String,
+        value: T,
+        interval: std::time::Duration,
+    ) -> Result<bool> {
+        let now = Instant::now();
+        let mut slots = self.slots.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        slots.retain(|_, slot| {
+            slot.flushing || slot.last_pushed.is_some_and(|at| now.duration_since(at) < interval)
+        });
+        let slot = slots.entry(key.clone()).or_insert(DebounceSlot {
+            last_pushed: None,
+            pending: None,
+            flushing: false,
+        });
+        let Some(last_pushed) = slot.last_pushed.filter(|_| !interval.is_zero()) else {
+            slot.last_pushed = Some(now);
+            drop(slots);
+            return (self.push)(value).map(|()| true);
+        };
+        slot.pending = Some(value);
+        if !slot.flushing {
+            slot.flushing = true;
+            let debouncer = Arc::clone(self);
+            std::thread::spawn(move || debouncer.flush(&key, last_pushed + interval, interval));
+        }
+        Ok(false)
+    }
+
+    /// Pushes the pending value for `key` at `due`, then keeps going an interval at a
+    /// time while more values arrive.
+    fn flush(&self, key: &str, mut due: Instant, interval: std::time::Duration) {
+        loop {
+            std::thread::sleep(due.saturating_duration_since(Instant::now()));
+            let mut slots = self.slots.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            let Some(slot) = slots.get_mut(key) else {
+                return;
+            };
+            let Some(value) = slot.pending.take() else {
+                slot.flushing = false;
+                return;
+            };
+            let now = Instant::now();
+            slot.last_pushed = Some(now);
+            due = now + interval;
+            drop(slots);
+            let _ = (self.push)(value);
+        }
+    }
+}
+
+pub fn validate_benchmark(input: &BenchmarkInput) -> Result<()> {
+    if !(1..=100).contains(&input.count) {
+        return Err(anyhow::anyhow!(
+            "count must be between 1 and 100 (got {})",
+            input.count
+        ));
+    }
+    Ok(())
+}
+
+/// Times `show_toast` over `count` silent, popup-suppressed toasts that share a tag,
+/// so they replace each other in the Action Center instead of piling up. They carry
+/// the first icon, like a typical `notify`, so the cached template is what gets timed
+/// unless `load_xml` is set.
+pub fn benchmark(input: BenchmarkInput, config: &Config) -> Result<BenchmarkReport> {
+    validate_benchmark(&input)?;
+
+    let icon_path = list_icon_ids(config)
+        .first()
+        .and_then(|icon| resolve_icon(icon, config).ok());
+    let mut durations = Vec::with_capacity(input.count as usize);
+    for run in 1..=input.count {
+        let spec = benchmark_spec(&input, run, icon_path.clone());
+        let started = Instant::now();
+        show_toast(&spec, config)?;
+        durations.push(elapsed_ms(started));
+    }
+
+    durations.sort_by(f64::total_cmp);
+    let p95_index = ((durations.len() as f64 * 0.95).ceil() as usize).saturating_sub(1);
+    Ok(BenchmarkReport {
+        count: input.count,
+        min_ms: durations[0],
+        avg_ms: durations.iter().sum::<f64>() / durations.len() as f64,
+        max_ms: durations[durations.len() - 1],
+        p95_ms: durations[p95_index],
+    })
+}
+
+fn benchmark_spec(input: &BenchmarkInput, run: u32, icon_path: Option<PathBuf>) -> ToastSpec {
+    ToastSpec {
+        title: "ToastMCP benchmark".to_string(),
+        body: vec![format!("Run {run} of {}", input.count)],
+        icon_path,
+        audio_src: None,
+        suppress_popup: true,
+        tag: Some(input.tag.clone()),
+        expires_in: None,
+        launch: None,
+        progress: None,
+        group: None,
+        attribution: None,
+        display_timestamp: None,
+        body_max_lines: None,
+        toast_id: None,
+        correlation_id: None,
+        load_xml: input.load_xml,
+    }
+}
+
+/// Builds the volume-adjusted cache for every WAV in `sounds/` so the first
+/// `notify` doesn't pay for it. Results are reported on stderr.
+pub fn warm_sound_cache(config: &Config) {
+    let volume = config.volume as f32;
+    let mut warmed = 0;
+    for sound_id in list_sound_ids(config) {
+        let Some(sound_path) = find_sound_path(&sound_id, config) else {
+            continue;
+        };
+        match prepare_quiet_wav(&sound_path, volume, false, false) {
+            Ok(_) => warmed += 1,
+            Err(err) => eprintln!("toastmcp: warmup failed for sound {sound_id}: {err:#}"),
+        }
+    }
+    eprintln!("toastmcp: warmed {warmed} sound(s)");
+}
+
+/// Parses an `icon_bg` color written as `#RRGGBB`.
+fn parse_hex_color(color: &str) -> Result<[u8; 3]> {
+    let hex = color
+        .strip_prefix('#')
+        .filter(|hex| hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit()))
+        .ok_or_else(|| anyhow::anyhow!("icon_bg must look like \"#RRGGBB\" (got {color:?})"))?;
+    let mut rgb = [0; 3];
+    for (index, channel) in rgb.iter_mut().enumerate() {
+        *channel = u8::from_str_radix(&hex[index * 2..index * 2 + 2], 16)?;
+    }
+    Ok(rgb)
+}
+
+/// Composites the icon onto a solid plate, cached as `{stem}_bg{rrggbb}.png` in `cache/`.
+/// With `read_only`, only decodes the icon and returns the path it would be cached at.
+#[cfg(feature = "icon-bg")]
+fn icon_with_background(path: &Path, rgb: [u8; 3], read_only: bool) -> Result<PathBuf> {
+    let cache_dir = cache_dir()?;
+
+    let stem = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("icon");
+    let [red, green, blue] = rgb;
+    let cache_path = cache_dir.join(format!("{stem}_bg{red:02x}{green:02x}{blue:02x}.png"));
+
+    if cache_path.exists() {
+        let src_time = std::fs::metadata(path)?.modified().ok();
+        let dst_time = std::fs::metadata(&cache_path)?.modified().ok();
+        if src_time.is_some() && dst_time.is_some() && dst_time >= src_time {
+            return Ok(cache_path);
+        }
+    }
+
+    let icon = image::open(path)
+        .with_context(|| format!("Failed to decode {}", path.display()))?
+        .to_rgba8();
+    if read_only {
+        return Ok(cache_path);
+    }
+    std::fs::create_dir_all(&cache_dir).context("Failed to create cache directory")?;
+    let mut plate = image::RgbaImage::from_pixel(
+        icon.width(),
+        icon.height(),
+        image::Rgba([red, green, blue, 255]),
+    );
+    image::imageops::overlay(&mut plate, &icon, 0, 0);
+    plate
+        .save(&cache_path)
+        .context("Failed to write composited icon")?;
+    Ok(cache_path)
+}
+
+#[cfg(not(feature = "icon-bg"))]
+fn icon_with_background(_path: &Path, _rgb: [u8; 3], _read_only: bool) -> Result<PathBuf> {
+    Err(anyhow::anyhow!(
+        "this build was compiled without the icon-bg feature"
+    ))
+}
+
+/// Outcome of `prepare_quiet_wav`: the file to play and whether it was volume-scaled.
+#[derive(Debug, Clone, Serialize)]
+pub struct PreparedSound {
+    pub path: PathBuf,
+    pub scaled: bool,
+    pub cached: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skipped_reason: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wav: Option<WavInfo>,
+}
+
+impl PreparedSound {
+    fn unscaled(path: &Path, reason: String, wav: Option<WavInfo>) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            scaled: false,
+            cached: false,
+            skipped_reason: Some(reason),
+            wav,
+        }
+    }
+}
+
+/// Scales `path` to `volume` into `cache/`, reusing a cached copy newer than the
+/// source unless `force` is set. With `read_only`, checks the WAV and returns the
+/// cache path without writing it.
+fn prepare_quiet_wav(
+    path: &Path,
+    volume: f32,
+    force: bool,
+    read_only: bool,
+) -> Result<PreparedSound> {
+    if !(0.0..=1.0).contains(&volume) {
+        return Ok(PreparedSound::unscaled(
+            path,
+            format!("volume {volume} is outside 0.0-1.0"),
+            None,
+        ));
+    }
+
+    let cache_dir = cache_dir()?;
+    let stem = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("sound");
+    let cache_name = format!("{stem}_vol{}.wav", (volume * 100.0).round() as u32);
+    let cache_path = cache_dir.join(cache_name);
+
+    if !force && cache_path.exists() {
+        let src_time = std::fs::metadata(path)?.modified().ok();
+        let dst_time = std::fs::metadata(&cache_path)?.modified().ok();
+        if src_time.is_some() && dst_time.is_some() && dst_time >= src_time {
+            return Ok(PreparedSound {
+                path: cache_path,
+                scaled: true,
+                cached: true,
+                skipped_reason: None,
+                wav: None,
+            });
+        }
+    }
+
+    let mut data = std::fs::read(path).context("Failed to read wav file")?;
+    let info = match wav::parse(&data) {
+        Ok(info) => info,
+        Err(err) => return Ok(PreparedSound::unscaled(path, err.to_string(), None)),
+    };
+    if let Some(reason) = info.unscalable_reason() {
+        return Ok(PreparedSound::unscaled(path, reason, Some(info)));
+    }
+
+    if !read_only {
+        wav::scale_pcm16(&mut data, &info, volume);
+        std::fs::create_dir_all(&cache_dir).context("Failed to create cache directory")?;
+        write_atomic(&cache_path, &data).context("Failed to write cached wav")?;
+    }
+    Ok(PreparedSound {
+        path: cache_path,
+        scaled: true,
+        cached: false,
+        skipped_reason: None,
+        wav: Some(info),
+    })
+}
+
+/// Removes ephemeral files from `cache/` at shutdown. Volume-scaled WAVs are reusable
+/// across runs, so they're only removed when `include_sound_cache` is set.
+pub fn cleanup_cache(include_sound_cache: bool) {
+    let Ok(dir) = cache_dir() else {
+        return;
+    };
+    // The last async sound, or any still waiting in the queue, may not have finished.
+    let mut in_use = SOUND_QUEUE.get().map_or_else(Vec::new, SoundQueue::held);
+    in_use.extend(
+        LAST_PLAYED
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone(),
+    );
+    let removed = cleanup_dir(&dir, include_sound_cache, &in_use);
+    eprintln!("toastmcp: removed {removed} cache file(s)");
+}
+
+/// Deletes the cache files in `dir` that `cleanup_cache` may remove, sparing `in_use`.
+fn cleanup_dir(dir: &Path, include_sound_cache: bool, in_use: &[PathBuf]) -> usize {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return 0;
+    };
+    let mut removed = 0;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if !path.is_file() || in_use.contains(&path) {
+            continue;
+        }
+        if is_volume_cache_file(&path) && !include_sound_cache {
+            continue;
+        }
+        if std::fs::remove_file(&path).is_ok() {
+            removed += 1;
+        }
+    }
+    removed
+}
+
+/// Matches the `{stem}_vol{level}.wav` names written by `prepare_quiet_wav`.
+fn is_volume_cache_file(path: &Path) -> bool {
+    volume_cache_stem(path).is_some()
+}
+
+/// The source sound's stem of a `{stem}_vol{level}.wav` cache file, so
+/// `beep_vol2_vol70.wav` belongs to `beep_vol2` and not to `beep`.
+fn volume_cache_stem(path: &Path) -> Option<&str> {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| name.strip_suffix(".wav"))
+        .and_then(|stem| stem.rsplit_once("_vol"))
+        .filter(|(_, level)| !level.is_empty() && level.chars().all(|c| c.is_ascii_digit()))
+        .map(|(stem, _)| stem)
+}
+
+/// The source icon's stem of a `{stem}_bg{rrggbb}.png` file from `icon_with_background`.
+fn background_cache_stem(path: &Path) -> Option<&str> {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| name.strip_suffix(".png"))
+        .and_then(|stem| stem.rsplit_once("_bg"))
+        .filter(|(_, rgb)| rgb.len() == 6 && rgb.chars().all(|c| c.is_ascii_hexdigit()))
+        .map(|(stem, _)| stem)
+}
+
+fn cache_dir() -> Result<PathBuf> {
+    let exe_dir = std::env::current_exe()
+        .ok()
+        .and_then(|p| p.parent().map(Path::to_path_buf))
+        .context("Failed to resolve exe directory")?;
+    Ok(exe_dir.join("cache"))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AssetKind {
+    Icon,
+    Sound,
+}
+
+impl AssetKind {
+    pub fn folder(self) -> &'static str {
+        match self {
+            AssetKind::Icon => "icons",
+            AssetKind::Sound => "sounds",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            AssetKind::Icon => "png",
+            AssetKind::Sound => "wav",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RenameAssetInput {
+    pub kind: AssetKind,
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RenamedAsset {
+    pub kind: AssetKind,
+    pub from: String,
+    pub to: String,
+    pub path: PathBuf,
+    pub invalidated_cache_files: usize,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ResolveAssetInput {
+    pub kind: AssetKind,
+    pub id: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ResolvedAsset {
+    pub kind: AssetKind,
+    pub id: String,
+    /// The file `notify` would use, or `None` when no candidate exists.
+    pub path: Option<PathBuf>,
+    /// Every top-level candidate path, in the order they are tried. Each folder's
+    /// subfolders are searched right after its top level.
+    pub searched: Vec<PathBuf>,
+}
+
+/// Reports which file an asset id resolves to, without showing or playing anything,
+/// or writing the default icons or built-in assets it would use.
+pub fn locate_asset(input: ResolveAssetInput, config: &Config) -> Result<ResolvedAsset> {
+    validate_asset_id(&input.id)?;
+    let config = &read_only(config);
+    let file_name = format!("{}.{}", input.id, input.kind.extension());
+    let searched: Vec<PathBuf> = asset_dirs(input.kind.folder())
+        .into_iter()
+        .map(|dir| dir.join(&file_name))
+        .collect();
+    let path = resolve_asset(input.kind.folder(), &file_name, config).ok();
+    Ok(ResolvedAsset {
+        kind: input.kind,
+        id: input.id,
+        path,
+        searched,
+    })
+}
+
+/// The file `rename_asset` would move, and where to, after checking both ids
+/// and that the target is free.
+pub fn rename_paths(input: &RenameAssetInput, config: &Config) -> Result<(PathBuf, PathBuf)> {
+    validate_asset_id(&input.from)?;
+    validate_asset_id(&input.to)?;
+
+    // Only files already on disk can be renamed, so nothing is written to find them.
+    let config = &read_only(config);
+    let folder = input.kind.folder();
+    let extension = input.kind.extension();
+    let source = resolve_asset(folder, &format!("{}.{extension}", input.from), config)?;
+    if !source.is_file() {
+        return Err(anyhow::anyhow!(
+            "Missing asset: {folder}/{}.{extension}",
+            input.from
+        ));
+    }
+    let target = source.with_file_name(format!("{}.{extension}", input.to));
+    // The new id may already exist in another subfolder, where it would shadow the rename.
+    if target.exists()
+        || resolve_asset(folder, &format!("{}.{extension}", input.to), config).is_ok()
+    {
+        return Err(anyhow::anyhow!(
+            "{}/{}.{extension} already exists",
+            input.kind.folder(),
+            input.to
+        ));
+    }
+    Ok((source, target))
+}
+
+/// Renames an asset file in place and drops cache entries derived from the old name.
+pub fn rename_asset(input: RenameAssetInput, config: &Config) -> Result<RenamedAsset> {
+    let (source, target) = rename_paths(&input, config)?;
+    std::fs::rename(&source, &target)
+        .with_context(|| format!("Failed to rename {}", source.display()))?;
+
+    let mut invalidated_cache_files = 0;
+    if let Ok(entries) = std::fs::read_dir(cache_dir()?) {
+        let from = nfc(&input.from);
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let stem = match input.kind {
+                AssetKind::Icon => background_cache_stem(&path),
+                AssetKind::Sound => volume_cache_stem(&path),
+            };
+            if stem.is_some_and(|stem| nfc(stem) == from) && std::fs::remove_file(&path).is_ok() {
+                invalidated_cache_files += 1;
+            }
+        }
+    }
+
+    Ok(RenamedAsset {
+        kind: input.kind,
+        from: input.from,
+        to: input.to,
+        path: target,
+        invalidated_cache_files,
+    })
+}
+
+/// Asset ids are bare file stems: no separators, drive or stream colons, traversal or
+/// hidden files. Anything else the scanners can list, such as `Level Up`, is accepted.
+pub fn validate_asset_id(id: &str) -> Result<()> {
+    let valid = !id.is_empty()
+        && !id.starts_with('.')
+        && !id.contains(['/', '\\', ':', '\0']);
+    if !valid {
+        return Err(anyhow::anyhow!(
+            "Invalid asset id {id:?}: it must be a file name without /, \\, : or a leading dot"
+        ));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PrepareSoundInput {
+    pub sound: String,
+    #[serde(default)]
+    pub volume: Option<f32>,
+    /// Regenerate the scaled copy even if the cache looks current.
+    #[serde(default)]
+    pub force: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PreviewSoundInput {
+    pub sound: String,
+    #[serde(default)]
+    pub volume: Option<f32>,
+}
+
+/// Plays a sound through the same volume pipeline as `notify`, without a toast,
+/// and returns once playback has started. `prepare_sound` checks the id and the
+/// allow list before any file is looked up.
+pub fn preview_sound(input: PreviewSoundInput, config: &Config) -> Result<PreparedSound> {
+    let prepared = prepare_sound(
+        PrepareSoundInput {
+            sound: input.sound,
+            volume: input.volume,
+            force: false,
+        },
+        config,
+    )?;
+    play_sound(&prepared.path, None, config)?;
+    *LAST_PLAYED
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(prepared.path.clone());
+    Ok(prepared)
+}
+
+/// Runs the WAV volume pipeline for one sound without playing it. A read-only config
+/// reports the cache file it would write instead.
+pub fn prepare_sound(input: PrepareSoundInput, config: &Config) -> Result<PreparedSound> {
+    validate_asset_id(&input.sound)?;
+    if !config.allows_sound(&input.sound) {
+        return Err(anyhow::anyhow!(
+            "Sound {} is not allowed by this server's config. Allowed sound ids: {}.",
+            input.sound,
+            format_ids(&list_sound_ids(config))
+        ));
+    }
+    let Some(sound_path) = find_sound_path(&input.sound, config) else {
+        return Err(anyhow::anyhow!(
+            "Sound not found: sounds/{}.wav. Valid sound ids: {}.",
+            input.sound,
+            format_ids(&list_sound_ids(config))
+        ));
+    };
+    prepare_quiet_wav(
+        &sound_path,
+        input.volume.unwrap_or(config.volume as f32),
+        input.force,
+        config.read_only,
+    )
+}
+
+/// Rejects empty, truncated or non-WAV files before they reach `PlaySoundW`,
+/// which would otherwise fail silently.
+fn check_playable_wav(sound_id: &str, path: &Path) -> Result<(), NotifyError> {
+    let decode_error = |reason: String| NotifyError::AudioDecode {
+        sound: sound_id.to_string(),
+        reason,
+    };
+    let data = std::fs::read(path).map_err(|err| decode_error(err.to_string()))?;
+    let info = wav::parse(&data).map_err(|err| decode_error(err.to_string()))?;
+    if info.data_size == 0 {
+        return Err(decode_error("data chunk is empty".to_string()));
+    }
+    Ok(())
+}
+
+fn read_only(config: &Config) -> Config {
+    Config {
+        read_only: true,
+        ..config.clone()
+    }
+}
+
+fn resolve_sound(sound_id: &str, config: &Config) -> Result<PathBuf> {
+    let file_name = format!("{sound_id}.wav");
+    resolve_asset("sounds", &file_name, config)
+}
+
+pub fn find_sound_path(sound_id: &str, config: &Config) -> Option<PathBuf> {
+    resolve_sound(sound_id, config).ok()
+}
+
+/// The icon a toast shows. Only this path creates the default icons on first use;
+/// a read-only config reports where they would go instead.
+fn resolve_icon(icon_id: &str, config: &Config) -> Result<PathBuf> {
+    if !config.read_only {
+        bootstrap_default_icons(&config.excluded_dirs);
+    }
+    let file_name = format!("{icon_id}.png");
+    resolve_asset("icons", &file_name, config)
+}
+
+/// Looks `file_name` up in each asset folder in turn, subfolders included, skipping
+/// the config's `excluded_dirs` just like the listings do. Built-in assets of an
+/// `embedded-assets` build come last, so a file on disk always wins.
+/// A read-only config doesn't write built-in assets, and reports where they (or
+/// the default icons) would go.
+fn resolve_asset(folder: &str, file_name: &str, config: &Config) -> Result<PathBuf> {
+    for dir in asset_dirs(folder) {
+        if let Some(path) = find_asset_file(&dir, file_name, &config.excluded_dirs) {
+            return Ok(path);
+        }
+    }
+    if let Some(path) = embedded_asset_path(folder, file_name, config.read_only) {
+        return path;
+    }
+    if folder == "icons"
+        && config.read_only
+        && let Some(path) = planned_default_icon(file_name, &config.excluded_dirs)
+    {
+        return Ok(path);
+    }
+
+    Err(anyhow::anyhow!(
+        "Missing asset: {}/{}",
+        folder,
+        file_name
+    ))
+}
+
+/// Removes common Markdown syntax (emphasis, inline code, headings, links) that
+/// toasts would otherwise render literally. Lone `*`, `#` and `[` are left alone, so
+/// `2 * 3`, `*.rs` and `#123 failed` come through unchanged.
+fn strip_markdown(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for (index, line) in text.lines().enumerate() {
+        if index > 0 {
+            out.push('\n');
+        }
+        let trimmed = line.trim_start();
+        let level = trimmed.chars().take_while(|&c| c == '#').count();
+        let line = match trimmed[level..].strip_prefix(' ') {
+            Some(rest) if (1..=6).contains(&level) => rest.trim_start(),
+            _ => line,
+        };
+
+        let chars: Vec<char> = strip_emphasis(line).chars().collect();
+        let mut i = 0;
+        while i < chars.len() {
+            let link = link_at(&chars, i);
+            match chars[i] {
+                '`' => i += 1,
+                '!' if link_at(&chars, i + 1).is_some() => i += 1,
+                '[' if link.is_some() => {
+                    let (label_end, end) = link.unwrap_or_default();
+                    out.extend(&chars[i + 1..label_end]);
+                    i = end;
+                }
+                ch => {
+                    out.push(ch);
+                    i += 1;
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Drops `*` and `**` where they pair up around text, as in `*this*` or `**this**`:
+/// the opening run must be followed, and the closing run preceded, by non-space, and
+/// neither may touch a letter or digit on its outer side.
+fn strip_emphasis(line: &str) -> String {
+    let chars: Vec<char> = line.chars().collect();
+    let star_run = |at: usize| chars[at..].iter().take_while(|&&c| c == '*').count();
+    let closing_run = |start: usize, run: usize| {
+        if chars.get(start).is_none_or(|c| c.is_whitespace()) {
+            return None;
+        }
+        let mut at = start + 1;
+        while at < chars.len() {
+            match star_run(at) {
+                0 => at += 1,
+                len if len == run
+                    && !chars[at - 1].is_whitespace()
+                    && !chars.get(at + len).is_some_and(|c| c.is_alphanumeric()) =>
+                {
+                    return Some(at);
+                }
+                len => at += len,
+            }
+        }
+        None
+    };
+
+    let mut out = String::with_capacity(line.len());
+    let mut i = 0;
+    while i < chars.len() {
+        let run = star_run(i);
+        if run == 0 {
+            out.push(chars[i]);
+            i += 1;
+            continue;
+        }
+        let after_word = i > 0 && chars[i - 1].is_alphanumeric();
+        match closing_run(i + run, run).filter(|_| run <= 2 && !after_word) {
+            Some(close) => {
+                let inner: String = chars[i + run..close].iter().collect();
+                out.push_str(&strip_emphasis(&inner));
+                i = close + run;
+            }
+            None => {
+                out.extend(&chars[i..i + run]);
+                i += run;
+            }
+        }
+    }
+    out
+}
+
+/// For a `[label](url)` link opening at `open`, the index of its `]` and the index
+/// just past its `)`.
+fn link_at(chars: &[char], open: usize) -> Option<(usize, usize)> {
+    if chars.get(open) != Some(&'[') {
+        return None;
+    }
+    let label_end = open + chars[open..].iter().position(|&c| c == ']')?;
+    if chars.get(label_end + 1) != Some(&'(') {
+        return None;
+    }
+    let url_end = label_end + chars[label_end..].iter().position(|&c| c == ')')?;
+    Some((label_end, url_end + 1))
+}
+
+/// Serializes `data` as a URL-encoded query string for the toast's `launch` attribute.
+fn encode_launch_data(data: &BTreeMap<String, String>) -> String {
+    data.iter()
+        .map(|(key, value)| format!("{}={}", percent_encode(key), percent_encode(value)))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// Percent-encodes everything except RFC 3986 unreserved characters.
+fn percent_encode(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                out.push(byte as char)
+            }
+            _ => out.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    out
+}
+
+fn format_ids(ids: &[String]) -> String {
+    if ids.is_empty() {
+        return "none (add assets to the folder)".to_string();
+    }
+    ids.join(", ")
+}
+
+/// Which sound is current, so a loop's stop timer can tell whether a newer sound or
+/// notify has taken over (and must not be cut off).
+struct SoundGenerations {
+    current: AtomicU64,
+    /// The generation of the `audio_loop` still playing, or 0 when none is.
+    looping: AtomicU64,
+}
+
+static SOUND_GENERATIONS: SoundGenerations = SoundGenerations::new();
+
+impl SoundGenerations {
+    const fn new() -> Self {
+        Self {
+            current: AtomicU64::new(0),
+            looping: AtomicU64::new(0),
+        }
+    }
+
+    /// Starts a new generation. The flag is true if a loop from an earlier one was
+    /// still playing, which the caller must stop.
+    fn advance(&self) -> (u64, bool) {
+        let generation = self.current.fetch_add(1, Ordering::SeqCst) + 1;
+        let looping = self.looping.swap(0, Ordering::SeqCst);
+        (generation, looping != 0)
+    }
+
+    #[cfg_attr(not(windows), allow(dead_code))]
+    fn start_loop(&self, generation: u64) {
+        self.looping.store(generation, Ordering::SeqCst);
+    }
+
+    /// When a loop's time is up: true if it is still playing and should be stopped.
+    #[cfg_attr(not(windows), allow(dead_code))]
+    fn end_loop(&self, generation: u64) -> bool {
+        self.looping
+            .compare_exchange(generation, 0, Ordering::SeqCst, Ordering::SeqCst)
+            .is_ok()
+    }
+}
+
+/// Ends any `audio_loop` still playing. Every delivered notify calls this first,
+/// whatever it plays next (a WAV, a system sound, beeps or nothing for `log_only`).
+fn begin_sound() -> u64 {
+    let (generation, was_looping) = SOUND_GENERATIONS.advance();
+    if was_looping {
+        stop_sound();
+    }
+    generation
+}
+
+#[cfg(windows)]
+fn play_sound(path: &Path, loop_for: Option<std::time::Duration>, config: &Config) -> Result<()> {
+    use windows::Win32::Media::Audio::{SND_ASYNC, SND_LOOP};
+
+    let generation = begin_sound();
+    if let Some(duration) = loop_for {
+        play_sound_file(path, SND_ASYNC | SND_LOOP)?;
+        SOUND_GENERATIONS.start_loop(generation);
+        std::thread::spawn(move || {
+            std::thread::sleep(duration);
+            if SOUND_GENERATIONS.end_loop(generation) {
+                stop_sound();
+            }
+        });
+        return Ok(());
+    }
+    if config.queue_sounds {
+        enqueue_sound(path, config.max_queued_sounds);
+        return Ok(());
+    }
+    play_sound_file(path, SND_ASYNC)
+}
+
+#[cfg(windows)]
+fn stop_sound() {
+    use windows::Win32::Media::Audio::{PlaySoundW, SND_FLAGS};
+
+    unsafe {
+        let _ = PlaySoundW(windows::core::PCWSTR::null(), None, SND_FLAGS(0));
+    }
+}
+
+/// Nothing loops without an audio backend.
+#[cfg(not(windows))]
+fn stop_sound() {}
+
+#[cfg(windows)]
+fn play_sound_file(path: &Path, flags: windows::Win32::Media::Audio::SND_FLAGS) -> Result<()> {
+    use std::ffi::OsStr;
+    use std::os::windows::ffi::OsStrExt;
+
+    use windows::Win32::Media::Audio::{PlaySoundW, SND_FILENAME, SND_NODEFAULT};
+
+    let wide: Vec<u16> = OsStr::new(path)
+        .encode_wide()
+        .chain(std::iter::once(0))
+        .collect();
+
+    unsafe {
+        PlaySoundW(
+            windows::core::PCWSTR(wide.as_ptr()),
+            None,
+            SND_FILENAME | flags | SND_NODEFAULT,
+        )
+        .ok()
+        .context("PlaySoundW failed")?;
+    }
+    Ok(())
+}
+
+/// Sounds waiting for the playback worker, which plays them synchronously in order.
+struct SoundQueue {
+    sender: std::sync::mpsc::Sender<PathBuf>,
+    /// Every sound queued and not yet finished, the one playing first.
+    held: Arc<Mutex<Vec<PathBuf>>>,
+}
+
+/// Started by the first queued sound, so `cleanup_cache` can see what it still holds.
+static SOUND_QUEUE: std::sync::OnceLock<SoundQueue> = std::sync::OnceLock::new();
+
+#[cfg_attr(not(windows), allow(dead_code))]
+impl SoundQueue {
+    /// Starts the worker, which calls `play` for each queued sound and waits for it to return.
+    fn spawn(play: impl Fn(&Path) + Send + 'static) -> Self {
+        let (sender, receiver) = std::sync::mpsc::channel::<PathBuf>();
+        let held = Arc::new(Mutex::new(Vec::new()));
+        let worker_held = Arc::clone(&held);
+        std::thread::spawn(move || {
+            for path in receiver {
+                play(&path);
+                let mut held = worker_held
+                    .lock()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner());
+                if let Some(index) = held.iter().position(|held| *held == path) {
+                    held.remove(index);
+                }
+            }
+        });
+        SoundQueue { sender, held }
+    }
+
+    /// Queues `path` unless `max_pending` sounds are already waiting or playing, in which
+    /// case the new one is skipped rather than delaying later toasts.
+    fn push(&self, path: &Path, max_pending: usize) -> bool {
+        let mut held = self.lock_held();
+        if held.len() >= max_pending || self.sender.send(path.to_path_buf()).is_err() {
+            return false;
+        }
+        held.push(path.to_path_buf());
+        true
+    }
+
+    fn held(&self) -> Vec<PathBuf> {
+        self.lock_held().clone()
+    }
+
+    fn lock_held(&self) -> std::sync::MutexGuard<'_, Vec<PathBuf>> {
+        self.held
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Hands `path` to the playback worker, started on first use.
+#[cfg(windows)]
+fn enqueue_sound(path: &Path, max_pending: usize) {
+    use windows::Win32::Media::Audio::SND_SYNC;
+
+    let queue = SOUND_QUEUE.get_or_init(|| {
+        SoundQueue::spawn(|path| {
+            if let Err(err) = play_sound_file(path, SND_SYNC) {
+                eprintln!("toastmcp: queued sound {} failed: {err:#}", path.display());
+            }
+        })
+    });
+    if !queue.push(path, max_pending) {
+        eprintln!(
+            "toastmcp: sound queue is full; skipped {}",
+            path.display()
+        );
+    }
+}
+
+/// `Beep` blocks for each tone, so the sequence plays on its own thread. It goes
+/// through the PC speaker path, so `volume` doesn't apply.
+#[cfg(windows)]
+fn play_beep_sequence(tones: &[BeepTone], _config: &Config) -> Result<()> {
+    use windows::Win32::System::Diagnostics::Debug::Beep;
+
+    let tones = tones.to_vec();
+    std::thread::spawn(move || {
+        for tone in tones {
+            if let Err(err) = unsafe { Beep(tone.freq, tone.ms) } {
+                eprintln!("toastmcp: beep failed: {err}");
+                return;
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Without an audio backend a beep sequence falls back to a single terminal bell.
+#[cfg(not(windows))]
+fn play_beep_sequence(_tones: &[BeepTone], config: &Config) -> Result<()> {
+    play_sound(Path::new(""), None, config)
+}
+
+/// Degraded fallback: without an audio backend the best we can do is the terminal
+/// bell. It goes to stderr because stdout carries the JSON-RPC stream.
+#[cfg(not(windows))]
+fn play_sound(
+    _path: &Path,
+    _loop_for: Option<std::time::Duration>,
+    config: &Config,
+) -> Result<()> {
+    use std::io::Write;
+
+    if !config.terminal_bell {
+        return Err(anyhow::anyhow!(
+            "Sound playback is only implemented on Windows"
+        ));
+    }
+    let mut stderr = std::io::stderr();
+    stderr
+        .write_all(b"\x07")
+        .context("Failed to ring terminal bell")?;
+    stderr.flush().context("Failed to ring terminal bell")?;
+    Ok(())
+}
+
+/// An upper bound on the length of `build_toast_xml(spec)`, worked out from the escaped
+/// text and attributes alone so it can be checked before anything is built.
+fn toast_xml_len(spec: &ToastSpec) -> usize {
+    let escaped = |text: Option<&str>| text.map_or(0, |text| xml_escape(text).len());
+    let body: usize = spec
+        .body
+        .iter()
+        .map(|line| xml_escape(line).len() + TOAST_XML_LINE_OVERHEAD_BYTES)
+        .sum();
+    TOAST_XML_OVERHEAD_BYTES
+        + escaped(Some(&spec.title))
+        + body
+        + escaped(spec.attribution.as_deref())
+        + escaped(spec.launch.as_deref())
+        + escaped(spec.toast_id.as_deref())
+        + spec.display_timestamp.as_deref().map_or(0, str::len)
+        + spec.icon_path.as_deref().and_then(icon_uri).map_or(0, |uri| uri.len())
+}
+
+#[cfg_attr(not(windows), allow(dead_code))]
+fn build_toast_xml(spec: &ToastSpec) -> String {
+    let image_fragment = spec
+        .icon_path
+        .as_deref()
+        .and_then(icon_uri)
+        .map(|uri| format!(r#"<image placement="appLogoOverride" src="{uri}"/>"#))
+        .unwrap_or_default();
+
+    let audio_fragment = spec
+        .audio_src
+        .map(|src| format!(r#"<audio src="{src}"/>"#))
+        .unwrap_or_else(|| "<audio silent=\"true\"/>".to_string());
+
+    let mut toast_attributes = spec
+        .launch
+        .as_deref()
+        .map(|launch| format!(r#" launch="{}""#, xml_escape(launch)))
+        .unwrap_or_default();
+    if let Some(timestamp) = spec.display_timestamp.as_deref() {
+        toast_attributes.push_str(&format!(r#" displayTimestamp="{timestamp}""#));
+    }
+    if let Some(toast_id) = spec.toast_id.as_deref() {
+        toast_attributes.push_str(&format!(r#" hint-toastId="{}""#, xml_escape(toast_id)));
+    }
+
+    let text_attributes = spec
+        .body_max_lines
+        .map(|lines| format!(r#" hint-maxLines="{lines}""#))
+        .unwrap_or_default();
+    let mut body_fragment: String = spec
+        .body
+        .iter()
+        .map(|line| format!("\n      <text{text_attributes}>{}</text>", xml_escape(line)))
+        .collect();
+    if spec.progress.is_some() {
+        body_fragment.push_str(
+            "\n      <progress value=\"{progressValue}\" status=\"{progressStatus}\"/>",
+        );
+    }
+    if let Some(attribution) = spec.attribution.as_deref() {
+        body_fragment.push_str(&format!(
+            "\n      <text placement=\"attribution\">{}</text>",
+            xml_escape(attribution)
+        ));
+    }
+
+    format!(
+        r#"<toast{}>
+  <visual>
+    <binding template="ToastGeneric">
+      <text>{}</text>{}
+      {}
+    </binding>
+  </visual>
+  {}
+</toast>"#,
+        toast_attributes,
+        xml_escape(&spec.title),
+        body_fragment,
+        image_fragment,
+        audio_fragment
+    )
+}
+
+/// Sets up COM, the app id and the Start Menu shortcut, and returns ToastMCP's notifier.
+#[cfg(windows)]
+fn toast_notifier(
+    config: &Config,
+    timings: &mut NotifyTimings,
+) -> Result<windows::UI::Notifications::ToastNotifier> {
+    use windows::UI::Notifications::ToastNotificationManager;
+    use windows::Win32::System::Com::{CoInitializeEx, COINIT_APARTMENTTHREADED};
+    use windows::Win32::UI::Shell::SetCurrentProcessExplicitAppUserModelID;
+    use windows::core::HSTRING;
+
+    let phase = Instant::now();
+    unsafe {
+        CoInitializeEx(None, COINIT_APARTMENTTHREADED)
+            .ok()
+            .context("CoInitializeEx failed")?;
+    }
+    timings.com_init_ms = elapsed_ms(phase);
+
+    let phase = Instant::now();
+    ensure_toasts_supported()?;
+
+    let app_id = HSTRING::from("ToastMCP");
+    unsafe {
+        SetCurrentProcessExplicitAppUserModelID(&app_id)
+            .context("SetCurrentProcessExplicitAppUserModelID failed")?;
+    }
+    ensure_start_menu_shortcut("ToastMCP", &config.display_name)?;
+    timings.shortcut_ms = elapsed_ms(phase);
+    Ok(ToastNotificationManager::CreateToastNotifierWithId(&app_id)?)
+}
+
+fn show_toast(spec: &ToastSpec, config: &Config) -> Result<()> {
+    show_toast_timed(spec, config, &mut NotifyTimings::default())
+}
+
+#[cfg(windows)]
+fn show_toast_timed(spec: &ToastSpec, config: &Config, timings: &mut NotifyTimings) -> Result<()> {
+    use windows::Data::Xml::Dom::XmlDocument;
+    use windows::UI::Notifications::ToastNotification;
+    use windows::core::HSTRING;
+
+    let notifier = toast_notifier(config, timings)?;
+    let phase = Instant::now();
+    let document = match fill_toast_template(spec) {
+        Some(Ok(document)) => document,
+        filled => {
+            if let Some(Err(err)) = filled {
+                log::write(
+                    LogLevel::Warn,
+                    "toast template failed",
+                    serde_json::json!({"error": err.to_string()}),
+                );
+            }
+            let document = XmlDocument::new()?;
+            document.LoadXml(&HSTRING::from(build_toast_xml(spec)))?;
+            document
+        }
+    };
+    let toast = ToastNotification::CreateToastNotification(&document)?;
+    if spec.suppress_popup {
+        toast.SetSuppressPopup(true)?;
+    }
+    if let Some(tag) = spec.tag.as_deref() {
+        toast.SetTag(&HSTRING::from(tag))?;
+    }
+    if let Some(group) = spec.group.as_deref() {
+        toast.SetGroup(&HSTRING::from(group))?;
+    }
+    if let Some(expires_in) = spec.expires_in {
+        toast.SetExpirationTime(&expiration_time(expires_in)?)?;
+    }
+    if let Some(progress) = &spec.progress {
+        toast.SetData(&progress_data(&progress.amount()?, Some(&progress.status))?)?;
+    }
+    notifier.Show(&toast)?;
+    timings.show_ms = elapsed_ms(phase);
+    Ok(())
+}
+
+/// Values for the `{progressValue}`/`{progressStatus}` bindings. Sequence number 0
+/// means Windows always applies the update, whatever order updates arrive in.
+#[cfg(windows)]
+fn progress_data(
+    value: &ProgressValue,
+    status: Option<&str>,
+) -> Result<windows::UI::Notifications::NotificationData> {
+    use windows::UI::Notifications::NotificationData;
+    use windows::core::HSTRING;
+
+    let data = NotificationData::new()?;
+    let values = data.Values()?;
+    values.Insert(
+        &HSTRING::from("progressValue"),
+        &HSTRING::from(value.binding()),
+    )?;
+    if let Some(status) = status {
+        values.Insert(&HSTRING::from("progressStatus"), &HSTRING::from(status))?;
+    }
+    data.SetSequenceNumber(0)?;
+    Ok(data)
+}
+
+#[cfg(windows)]
+fn push_progress_update(input: &UpdateProgressInput, config: &Config) -> Result<()> {
+    use windows::UI::Notifications::NotificationUpdateResult;
+    use windows::core::HSTRING;
+
+    let notifier = toast_notifier(config, &mut NotifyTimings::default())?;
+    let data = progress_data(&input.amount()?, input.status.as_deref())?;
+    let tag = HSTRING::from(input.tag.as_str());
+    let result = match input.group.as_deref() {
+        Some(group) => notifier.UpdateWithTagAndGroup(&data, &tag, &HSTRING::from(group))?,
+        None => notifier.UpdateWithTag(&data, &tag)?,
+    };
+    if result == NotificationUpdateResult::NotificationNotFound {
+        return Err(anyhow::anyhow!(
+            "No toast with tag {} is showing; it may have been dismissed",
+            input.tag
+        ));
+    }
+    if result != NotificationUpdateResult::Succeeded {
+        return Err(anyhow::anyhow!("Windows rejected the progress update"));
+    }
+    Ok(())
+}
+
+/// Converts a delay from now into the WinRT `DateTime` (100ns ticks since 1601) Windows expects.
+#[cfg(windows)]
+fn expiration_time(
+    expires_in: std::time::Duration,
+) -> Result<windows::Foundation::IReference<windows::Foundation::DateTime>> {
+    use windows::Foundation::{DateTime, IReference, PropertyValue};
+    use windows::core::Interface;
+
+    const UNIX_EPOCH_AS_FILETIME_SECS: u64 = 11_644_473_600;
+
+    let since_unix = (std::time::SystemTime::now() + expires_in)
+        .duration_since(std::time::UNIX_EPOCH)
+        .context("System clock is before 1970")?;
+    let ticks = (since_unix.as_secs() + UNIX_EPOCH_AS_FILETIME_SECS) * 10_000_000
+        + u64::from(since_unix.subsec_nanos() / 100);
+    let value = PropertyValue::CreateDateTime(DateTime {
+        UniversalTime: ticks as i64,
+    })?;
+    Ok(value.cast::<IReference<DateTime>>()?)
+}
+
+/// Builds a `file:///` URI, percent-encoding each path segment so spaces, `#`, `%`
+/// and non-ASCII names don't break the image reference. The drive colon is kept.
+fn icon_uri(path: &Path) -> Option<String> {
+    let segments: Vec<String> = path
+        .to_str()?
+        .split(['\\', '/'])
+        .map(|segment| percent_encode(segment).replace("%3A", ":"))
+        .collect();
+    Some(format!("file:///{}", segments.join("/")))
+}
+
+/// Parsed once per thread; `fill_toast_template` only swaps text and attributes.
+#[cfg(windows)]
+const TOAST_TEMPLATE_XML: &str = r#"<toast><visual><binding template="ToastGeneric"><text></text><text></text><image placement="appLogoOverride" src=""/></binding></visual><audio silent="true"/></toast>"#;
+
+#[cfg(windows)]
+thread_local! {
+    static TOAST_TEMPLATE: std::cell::RefCell<Option<windows::Data::Xml::Dom::XmlDocument>> =
+        const { std::cell::RefCell::new(None) };
+}
+
+/// Whether `spec` only needs what `TOAST_TEMPLATE_XML` has: an icon, one body line, and
+/// none of the optional elements or attributes.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn fits_toast_template(spec: &ToastSpec) -> bool {
+    !spec.load_xml
+        && spec.icon_path.is_some()
+        && spec.body.len() == 1
+        && spec.attribution.is_none()
+        && spec.progress.is_none()
+        && spec.display_timestamp.is_none()
+        && spec.toast_id.is_none()
+        && spec.body_max_lines.is_none()
+}
+
+/// Fast path for high-frequency notifications: clones a cached, already parsed
+/// `XmlDocument` instead of re-parsing XML for every toast. Each toast gets its own copy,
+/// since a shown toast keeps a reference to its document. Returns `None` when the spec
+/// doesn't fit the template, in which case `show_toast` falls back to `build_toast_xml`.
+#[cfg(windows)]
+fn fill_toast_template(
+    spec: &ToastSpec,
+) -> Option<windows::core::Result<windows::Data::Xml::Dom::XmlDocument>> {
+    use windows::Data::Xml::Dom::{XmlDocument, XmlElement};
+    use windows::core::{Interface, HSTRING};
+
+    if !fits_toast_template(spec) {
+        return None;
+    }
+    let icon_uri = spec.icon_path.as_deref().and_then(icon_uri)?;
+    let body = &spec.body[0];
+
+    Some(TOAST_TEMPLATE.with(|template| {
+        let mut template = template.borrow_mut();
+        let parsed = match template.as_ref() {
+            Some(document) => document.clone(),
+            None => {
+                let document = XmlDocument::new()?;
+                document.LoadXml(&HSTRING::from(TOAST_TEMPLATE_XML))?;
+                *template = Some(document.clone());
+                document
+            }
+        };
+        let document: XmlDocument = parsed.CloneNode(true)?.cast()?;
+
+        let root = document.DocumentElement()?;
+        match spec.launch.as_deref() {
+            Some(launch) => {
+                root.SetAttribute(&HSTRING::from("launch"), &HSTRING::from(launch))?;
+            }
+            None => root.RemoveAttribute(&HSTRING::from("launch"))?,
+        }
+
+        let texts = document.GetElementsByTagName(&HSTRING::from("text"))?;
+        texts
+            .Item(0)?
+            .SetInnerText(&HSTRING::from(spec.title.as_str()))?;
+        texts
+            .Item(1)?
+            .SetInnerText(&HSTRING::from(body.as_str()))?;
+
+        let image: XmlElement = document
+            .GetElementsByTagName(&HSTRING::from("image"))?
+            .Item(0)?
+            .cast()?;
+        image.SetAttribute(&HSTRING::from("src"), &HSTRING::from(icon_uri))?;
+
+        let audio: XmlElement = document
+            .GetElementsByTagName(&HSTRING::from("audio"))?
+            .Item(0)?
+            .cast()?;
+        match spec.audio_src {
+            Some(src) => {
+                audio.SetAttribute(&HSTRING::from("src"), &HSTRING::from(src))?;
+                audio.SetAttribute(&HSTRING::from("silent"), &HSTRING::from("false"))?;
+            }
+            None => {
+                audio.RemoveAttribute(&HSTRING::from("src"))?;
+                audio.SetAttribute(&HSTRING::from("silent"), &HSTRING::from("true"))?;
+            }
+        }
+
+        Ok(document)
+    }))
+}
+
+#[cfg(not(windows))]
+fn show_toast_timed(
+    _spec: &ToastSpec,
+    _config: &Config,
+    _timings: &mut NotifyTimings,
+) -> Result<()> {
+    Err(NotifyError::PlatformUnsupported {
+        reason: "toast notifications are only implemented on Windows".to_string(),
+    }
+    .into())
+}
+
+/// Counts ToastMCP's Action Center entries with `tag` (and `group`, if given).
+#[cfg(windows)]
+fn count_history(tag: &str, group: Option<&str>) -> Result<usize> {
+    use windows::UI::Notifications::ToastNotificationManager;
+    use windows::core::HSTRING;
+
+    let history = ToastNotificationManager::History()
+        .and_then(|history| history.GetHistoryWithId(&HSTRING::from("ToastMCP")))
+        .context("Toast history is unavailable")?;
+    let mut count = 0;
+    for toast in history {
+        let tag_matches = toast.Tag().is_ok_and(|toast_tag| toast_tag == tag);
+        let group_matches =
+            group.is_none_or(|group| toast.Group().is_ok_and(|toast_group| toast_group == group));
+        if tag_matches && group_matches {
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+#[cfg(not(windows))]
+fn count_history(_tag: &str, _group: Option<&str>) -> Result<usize> {
+    Err(NotifyError::PlatformUnsupported {
+        reason: "toast history is only available on Windows".to_string(),
+    }
+    .into())
+}
+
+#[cfg(not(windows))]
+fn push_progress_update(_input: &UpdateProgressInput, _config: &Config) -> Result<()> {
+    Err(NotifyError::PlatformUnsupported {
+        reason: "toast notifications are only implemented on Windows".to_string(),
+    }
+    .into())
+}
+
+/// Checks once whether the WinRT toast API exists on this Windows build. Only a missing
+/// class is cached as unsupported; any other failure is left for `show_toast` to report.
+#[cfg(windows)]
+fn ensure_toasts_supported() -> Result<(), NotifyError> {
+    use std::sync::OnceLock;
+
+    use windows::UI::Notifications::ToastNotificationManager;
+    use windows::Win32::Foundation::{CLASS_E_CLASSNOTAVAILABLE, REGDB_E_CLASSNOTREG};
+    use windows::core::HSTRING;
+
+    static UNSUPPORTED: OnceLock<Option<String>> = OnceLock::new();
+
+    let unsupported = UNSUPPORTED.get_or_init(|| {
+        let err = ToastNotificationManager::CreateToastNotifierWithId(&HSTRING::from("ToastMCP"))
+            .err()?;
+        if err.code() != REGDB_E_CLASSNOTREG && err.code() != CLASS_E_CLASSNOTAVAILABLE {
+            return None;
+        }
+        Some(format!(
+            "the WinRT ToastNotificationManager is unavailable ({err}); toasts need Windows 8 or later"
+        ))
+    });
+    match unsupported {
+        Some(reason) => Err(NotifyError::PlatformUnsupported {
+            reason: reason.clone(),
+        }),
+        None => Ok(()),
+    }
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn system_sound_to_audio_src(sound_id: &str) -> Option<&'static str> {
+    match sound_id {
+        "default" => Some("ms-winsoundevent:Notification.Default"),
+        "im" => Some("ms-winsoundevent:Notification.IM"),
+        "mail" => Some("ms-winsoundevent:Notification.Mail"),
+        "reminder" => Some("ms-winsoundevent:Notification.Reminder"),
+        "sms" => Some("ms-winsoundevent:Notification.SMS"),
+        "alarm" => Some("ms-winsoundevent:Notification.Alarm"),
+        "incoming_call" => Some("ms-winsoundevent:Notification.IncomingCall"),
+        _ => None,
+    }
+}
+
+
+#[cfg(windows)]
+fn ensure_start_menu_shortcut(app_id: &str, display_name: &str) -> Result<()> {
+    use std::ffi::OsStr;
+    use std::os::windows::ffi::OsStrExt;
+
+    use windows::Win32::System::Com::{CoCreateInstance, IPersistFile, CLSCTX_INPROC_SERVER};
+    use windows::Win32::UI::Shell::IShellLinkW;
+    use windows::Win32::UI::Shell::PropertiesSystem::IPropertyStore;
+    use windows::Win32::Storage::EnhancedStorage::PKEY_AppUserModel_ID;
+    use windows::core::{Interface, PROPVARIANT};
+
+    // Windows shows the shortcut's name as the notification source; the AUMID stays `app_id`.
+    let programs_dir = start_menu_programs_dir()?;
+    let shortcut_path = programs_dir.join(shortcut_file_name(display_name));
+
+    if shortcut_path.exists() {
+        let _ = std::fs::remove_file(&shortcut_path);
+    }
+    remove_renamed_shortcuts(&programs_dir, app_id, display_name);
+
+    let exe_path = std::env::current_exe().context("Failed to resolve current exe")?;
+    let icon_path = exe_path
+        .parent()
+        .map(|dir| dir.join("res\\ToastMCP.ico"))
+        .filter(|path| path.exists());
+
+    let link: IShellLinkW = unsafe { CoCreateInstance(&windows::Win32::UI::Shell::ShellLink, None, CLSCTX_INPROC_SERVER)? };
+    let exe_wide: Vec<u16> = OsStr::new(&exe_path)
+        .encode_wide()
+        .chain(std::iter::once(0))
+        .collect();
+    unsafe {
+        link.SetPath(windows::core::PCWSTR(exe_wide.as_ptr()))
+            .context("SetPath failed")?;
+        if let Some(icon_path) = icon_path.as_ref() {
+            let icon_wide: Vec<u16> = OsStr::new(icon_path)
+                .encode_wide()
+                .chain(std::iter::once(0))
+                .collect();
+            link.SetIconLocation(windows::core::PCWSTR(icon_wide.as_ptr()), 0)
+                .context("SetIconLocation failed")?;
+        }
+    }
+
+    unsafe {
+        let propvariant = PROPVARIANT::from(app_id);
+        let store = link.cast::<IPropertyStore>()?;
+        store
+            .SetValue(&PKEY_AppUserModel_ID, &propvariant)
+            .context("SetValue AppUserModelID failed")?;
+        store.Commit().context("Commit AppUserModelID failed")?;
+    }
+
+    let persist: IPersistFile = link.cast()?;
+    let shortcut_wide: Vec<u16> = OsStr::new(&shortcut_path)
+        .encode_wide()
+        .chain(std::iter::once(0))
+        .collect();
+    unsafe {
+        persist
+            .Save(windows::core::PCWSTR(shortcut_wide.as_ptr()), true)
+            .context("Save shortcut failed")?;
+    }
+
+    Ok(())
+}
+
+/// The Start Menu shortcut's file name for `display_name`. Windows drops trailing
+/// spaces and dots from file names, so they're trimmed here too.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn shortcut_file_name(display_name: &str) -> String {
+    format!("{}.lnk", display_name.trim().trim_end_matches(['.', ' ']))
+}
+
+/// Whether a shortcut is ours but named after a previous `display_name`, so toasts
+/// would otherwise appear under two names. File names compare case-insensitively.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn is_renamed_shortcut(
+    app_id: Option<&str>,
+    file_name: &str,
+    our_app_id: &str,
+    display_name: &str,
+) -> bool {
+    app_id == Some(our_app_id)
+        && !file_name.eq_ignore_ascii_case(&shortcut_file_name(display_name))
+}
+
+/// Removes our shortcuts left under an old `display_name`. Runs once per process and
+/// name, since `ensure_start_menu_shortcut` is called for every toast.
+#[cfg(windows)]
+fn remove_renamed_shortcuts(programs_dir: &Path, app_id: &str, display_name: &str) {
+    static CHECKED: Mutex<Option<String>> = Mutex::new(None);
+
+    let mut checked = CHECKED.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if checked.as_deref() == Some(display_name) {
+        return;
+    }
+    *checked = Some(display_name.to_string());
+    let Ok(entries) = std::fs::read_dir(programs_dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        let is_shortcut = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("lnk"));
+        if !is_shortcut {
+            continue;
+        }
+        let Ok((shortcut_app_id, _)) = read_shortcut(&path) else {
+            continue;
+        };
+        if is_renamed_shortcut(shortcut_app_id.as_deref(), file_name, app_id, display_name) {
+            let _ = std::fs::remove_file(&path);
+        }
+    }
+}
+
+#[cfg(windows)]
+fn start_menu_programs_dir() -> Result<PathBuf> {
+    let appdata = std::env::var("APPDATA").context("APPDATA not set")?;
+    Ok(PathBuf::from(appdata).join("Microsoft\\Windows\\Start Menu\\Programs"))
+}
+
+/// Removes Start Menu shortcuts left behind by earlier installs or display names:
+/// ones carrying ToastMCP's app id that are named after another `display_name` or
+/// whose target exe no longer exists. Other apps' shortcuts are never touched.
+#[cfg(windows)]
+pub fn cleanup_stale_shortcuts(display_name: &str) -> Result<usize> {
+    use windows::Win32::System::Com::{CoInitializeEx, COINIT_APARTMENTTHREADED};
+
+    unsafe {
+        CoInitializeEx(None, COINIT_APARTMENTTHREADED)
+            .ok()
+            .context("CoInitializeEx failed")?;
+    }
+
+    let mut removed = 0;
+    for entry in std::fs::read_dir(start_menu_programs_dir()?)?.flatten() {
+        let path = entry.path();
+        let is_shortcut = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("lnk"));
+        if !is_shortcut {
+            continue;
+        }
+        let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        let Ok((app_id, target)) = read_shortcut(&path) else {
+            continue;
+        };
+        if is_stale_shortcut(app_id.as_deref(), file_name, &target, "ToastMCP", display_name)
+            && std::fs::remove_file(&path).is_ok()
+        {
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+#[cfg(not(windows))]
+pub fn cleanup_stale_shortcuts(_display_name: &str) -> Result<usize> {
+    Ok(0)
+}
+
+/// A shortcut is ours to remove only if it carries our app id and is either named
+/// after another display name or points at an exe that is gone. An empty target means
+/// it couldn't be read, which is not proof the exe is missing.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn is_stale_shortcut(
+    app_id: Option<&str>,
+    file_name: &str,
+    target: &Path,
+    our_app_id: &str,
+    display_name: &str,
+) -> bool {
+    let target_gone = !target.as_os_str().is_empty() && !target.exists();
+    is_renamed_shortcut(app_id, file_name, our_app_id, display_name)
+        || (app_id == Some(our_app_id) && target_gone)
+}
+
+/// Returns a shortcut's AppUserModelID (if set) and target path, with environment
+/// variables such as `%LOCALAPPDATA%` expanded.
+#[cfg(windows)]
+fn read_shortcut(path: &Path) -> Result<(Option<String>, PathBuf)> {
+    use std::ffi::OsStr;
+    use std::os::windows::ffi::OsStrExt;
+
+    use windows::Win32::Storage::EnhancedStorage::PKEY_AppUserModel_ID;
+    use windows::Win32::System::Com::{CoCreateInstance, IPersistFile, CLSCTX_INPROC_SERVER, STGM_READ};
+    use windows::Win32::UI::Shell::PropertiesSystem::IPropertyStore;
+    use windows::Win32::UI::Shell::IShellLinkW;
+    use windows::core::{Interface, BSTR};
+
+    let link: IShellLinkW = unsafe { CoCreateInstance(&windows::Win32::UI::Shell::ShellLink, None, CLSCTX_INPROC_SERVER)? };
+    let path_wide: Vec<u16> = OsStr::new(path)
+        .encode_wide()
+        .chain(std::iter::once(0))
+        .collect();
+    let persist: IPersistFile = link.cast()?;
+    unsafe {
+        persist
+            .Load(windows::core::PCWSTR(path_wide.as_ptr()), STGM_READ)
+            .context("Load shortcut failed")?;
+    }
+
+    // No SLGP_RAWPATH: the default flags expand environment variables in the target.
+    let mut target = [0u16; 1024];
+    unsafe {
+        link.GetPath(&mut target, std::ptr::null_mut(), 0)
+            .context("GetPath failed")?;
+    }
+    let target_len = target.iter().position(|&c| c == 0).unwrap_or(target.len());
+    let target = PathBuf::from(String::from_utf16_lossy(&target[..target_len]));
+
+    let app_id = unsafe { link.cast::<IPropertyStore>()?.GetValue(&PKEY_AppUserModel_ID) }
+        .ok()
+        .and_then(|value| BSTR::try_from(&value).ok())
+        .map(|value| value.to_string())
+        .filter(|value| !value.is_empty());
+    Ok((app_id, target))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::*;
+    use crate::wav::tests::wav_bytes;
+
+    fn notify_error(input: Value) -> String {
+        let mut input: NotifyInput = serde_json::from_value(input).unwrap();
+        input.dry_run = true;
+        format!("{:#}", notify(input, &Config::default()).unwrap_err())
+    }
+
+    #[test]
+    fn shortcut_file_name_follows_the_display_name() {
+        assert_eq!(shortcut_file_name("ToastMCP"), "ToastMCP.lnk");
+        assert_eq!(shortcut_file_name("My Agent"), "My Agent.lnk");
+        assert_eq!(shortcut_file_name(" Agent... "), "Agent.lnk");
+        assert_eq!(shortcut_file_name("Build Bot ✓"), "Build Bot ✓.lnk");
+    }
+
+    #[test]
+    fn only_our_shortcuts_under_other_names_are_renamed_ones() {
+        let ours = Some("ToastMCP");
+        assert!(is_renamed_shortcut(
+            ours,
+            "ToastMCP.lnk",
+            "ToastMCP",
+            "My Agent"
+        ));
+        assert!(!is_renamed_shortcut(
+            ours,
+            "My Agent.lnk",
+            "ToastMCP",
+            "My Agent"
+        ));
+        assert!(!is_renamed_shortcut(
+            ours,
+            "my agent.LNK",
+            "ToastMCP",
+            "My Agent"
+        ));
+        assert!(!is_renamed_shortcut(
+            Some("Other.App"),
+            "Other.lnk",
+            "ToastMCP",
+            "My Agent"
+        ));
+        assert!(!is_renamed_shortcut(
+            None, "Old.lnk", "ToastMCP", "My Agent"
+        ));
+    }
+
+    #[test]
+    fn stale_shortcuts_are_ours_and_renamed_or_orphaned() {
+        let dir = temp_dir("shortcuts");
+        let exe = dir.join("toastmcp.exe");
+        std::fs::write(&exe, b"").unwrap();
+        let gone = dir.join("old").join("toastmcp.exe");
+        let stale = |app_id, file_name, target: &Path| {
+            is_stale_shortcut(app_id, file_name, target, "ToastMCP", "My Agent")
+        };
+
+        assert!(!stale(Some("ToastMCP"), "My Agent.lnk", &exe));
+        assert!(stale(Some("ToastMCP"), "My Agent.lnk", &gone));
+        assert!(stale(Some("ToastMCP"), "ToastMCP.lnk", &exe));
+        assert!(!stale(Some("ToastMCP"), "My Agent.lnk", Path::new("")));
+        assert!(!stale(Some("Other.App"), "Other.lnk", &gone));
+        assert!(!stale(None, "ToastMCP.lnk", &gone));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// The plan a dry-run `notify` resolves `input` to.
+    fn plan(input: Value) -> NotifyPlan {
+        let mut input: NotifyInput = serde_json::from_value(input).unwrap();
+        input.dry_run = true;
+        notify(input, &Config::default()).unwrap().plan.unwrap()
+    }
+
+    #[test]
+    fn log_only_toasts_are_silent_whatever_the_sound() {
+        for sound in ["default", "mail"] {
+            let plan = plan(serde_json::json!({
+                "title": "Logged", "message": "quietly", "icon": "happy",
+                "sound": sound, "mode": "log_only"
+            }));
+            assert!(matches!(plan.sound, PlannedSound::None));
+            assert_eq!(plan.mode, NotifyMode::LogOnly);
+        }
+        let sound = plan_sound(
+            "default",
+            SoundKind::System,
+            NotifyMode::LogOnly,
+            None,
+            &Config::default(),
+        );
+        assert!(matches!(sound.unwrap(), PlannedSound::None));
+        let sound = plan_sound(
+            "default",
+            SoundKind::System,
+            NotifyMode::Normal,
+            None,
+            &Config::default(),
+        );
+        assert!(matches!(sound.unwrap(), PlannedSound::System { .. }));
+    }
+
+    #[test]
+    fn sound_kind_picks_between_a_file_and_a_system_sound() {
+        let fixture = crate::assets::tests::SoundFixture::new("sms");
+        let config = Config::default();
+        let planned = |sound, kind| plan_sound(sound, kind, NotifyMode::Normal, None, &config);
+
+        let file = planned("sms", SoundKind::Auto).unwrap();
+        assert!(matches!(file, PlannedSound::File { path } if path == fixture.0));
+        let file = planned("sms", SoundKind::File).unwrap();
+        assert!(matches!(file, PlannedSound::File { .. }));
+        let system = planned("sms", SoundKind::System).unwrap();
+        assert!(matches!(system, PlannedSound::System { id } if id == "sms"));
+
+        assert!(matches!(
+            planned("alarm", SoundKind::Auto).unwrap(),
+            PlannedSound::System { .. }
+        ));
+        let err = format!("{:#}", planned("alarm", SoundKind::File).unwrap_err());
+        assert!(err.contains("alarm"), "{err}");
+        drop(fixture);
+    }
+
+    #[test]
+    fn sound_sets_pick_a_random_matching_sound() {
+        let fixtures = ["random-set-a", "random-set-b", "random-set-c"]
+            .map(crate::assets::tests::SoundFixture::new);
+        let config = Config::default();
+        assert!(is_sound_set("random-set-*"));
+        assert!(is_sound_set("random-set-?"));
+        assert!(!is_sound_set("random-set-a"));
+
+        let picks: std::collections::BTreeSet<String> = (0..64)
+            .map(|_| pick_random_sound("random-set-*", &config).unwrap())
+            .collect();
+        assert!(picks.len() > 1, "always picked {picks:?}");
+        assert!(picks.iter().all(|id| id.starts_with("random-set-")));
+
+        let mut input: NotifyInput = serde_json::from_value(serde_json::json!({
+            "title": "T", "message": "m", "icon": "happy", "sound": "random-set-?"
+        }))
+        .unwrap();
+        input.dry_run = true;
+        let outcome = notify(input, &config).unwrap();
+        let picked = outcome.sound.unwrap();
+        assert!(picked.starts_with("random-set-"), "{picked}");
+        let planned = outcome.plan.unwrap().sound;
+        assert!(
+            matches!(planned, PlannedSound::File { path } if path.ends_with(format!("{picked}.wav")))
+        );
+        drop(fixtures);
+    }
+
+    #[test]
+    fn sound_sets_with_a_slash_pick_from_a_subfolder() {
+        use crate::assets::tests::SoundFixture;
+        let fixtures = [
+            "chimes/subset-a",
+            "chimes/subset-b",
+            "chimes/subset-shadowed",
+            "subset-shadowed",
+            "subset-outside",
+        ]
+        .map(SoundFixture::new);
+        let config = Config::default();
+        let paths = list_sound_paths(&config);
+        assert!(paths.contains(&("subset-a".into(), "chimes/subset-a".into())));
+        assert!(paths.contains(&("subset-outside".into(), "subset-outside".into())));
+        // The top-level file is the one that plays, so the chimes/ copy isn't in the set.
+        assert!(paths.contains(&("subset-shadowed".into(), "subset-shadowed".into())));
+
+        for pattern in ["chimes/*", "Chimes\\subset-?"] {
+            let picks: std::collections::BTreeSet<String> = (0..64)
+                .map(|_| pick_random_sound(pattern, &config).unwrap())
+                .collect();
+            let expected = ["subset-a", "subset-b"].map(String::from);
+            assert_eq!(picks, expected.into(), "{pattern}");
+        }
+        assert!(pick_random_sound("chimes*", &config).is_err());
+
+        let plan = plan(serde_json::json!({
+            "title": "T", "message": "m", "icon": "happy", "sound": "chimes/*"
+        }));
+        assert!(matches!(
+            plan.sound,
+            PlannedSound::File { path } if path.parent().unwrap().ends_with("sounds/chimes")
+        ));
+        drop(fixtures);
+    }
+
+    #[test]
+    fn empty_sound_sets_are_an_error() {
+        let err = format!(
+            "{:#}",
+            pick_random_sound("no-such-set-*", &Config::default()).unwrap_err()
+        );
+        assert!(err.contains("No sounds match no-such-set-*"), "{err}");
+        let err = notify_error(serde_json::json!({
+            "title": "T", "message": "m", "icon": "happy", "sound": "no-such-set-*"
+        }));
+        assert!(err.contains("No sounds match"), "{err}");
+    }
+
+    #[test]
+    fn composed_ids_resolve_decomposed_sound_files() {
+        let fixture = crate::assets::tests::SoundFixture::new("nfc-cafe\u{301}");
+        let plan = plan(serde_json::json!({
+            "title": "T", "message": "m", "icon": "happy",
+            "sound": "nfc-caf\u{e9}", "sound_kind": "file"
+        }));
+        assert!(matches!(plan.sound, PlannedSound::File { path } if path == fixture.0));
+        drop(fixture);
+    }
+
+    #[test]
+    fn iso8601_utc_formats_civil_dates() {
+        let at = |secs| iso8601_utc(std::time::UNIX_EPOCH + std::time::Duration::from_secs(secs));
+        assert_eq!(at(0), "1970-01-01T00:00:00Z");
+        assert_eq!(at(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(at(1_792_154_096), "2026-10-16T12:34:56Z");
+        assert_eq!(at(4_102_444_799), "2099-12-31T23:59:59Z");
+    }
+
+    #[test]
+    fn relative_time_sets_the_display_timestamp() {
+        let input = |relative: bool| {
+            serde_json::json!({
+                "title": "T", "message": "m", "icon": "happy", "sound": "default",
+                "show_relative_time": relative
+            })
+        };
+        assert_eq!(plan(input(false)).display_timestamp, None);
+        let timestamp = plan(input(true)).display_timestamp.unwrap();
+        assert_eq!(timestamp.len(), "2026-10-16T12:34:56Z".len());
+        assert!(
+            timestamp.ends_with('Z') && timestamp.contains('T'),
+            "{timestamp}"
+        );
+
+        let mut spec = full_spec("x");
+        spec.display_timestamp = Some(timestamp.clone());
+        let xml = build_toast_xml(&spec);
+        assert!(xml.contains(&format!(r#" displayTimestamp="{timestamp}""#)));
+        spec.display_timestamp = None;
+        assert!(!build_toast_xml(&spec).contains("displayTimestamp"));
+        assert!(!fits_toast_template(&ToastSpec {
+            display_timestamp: Some(timestamp),
+            ..spec
+        }));
+    }
+
+    #[test]
+    fn toast_id_becomes_hint_toast_id() {
+        let input = |toast_id: &str| {
+            serde_json::json!({
+                "title": "T", "message": "m", "icon": "happy", "sound": "default",
+                "tag": "build", "toast_id": toast_id
+            })
+        };
+        let plan = plan(input("build-status"));
+        assert_eq!(plan.toast_id.as_deref(), Some("build-status"));
+        assert_eq!(plan.tag.as_deref(), Some("build"));
+
+        let mut spec = full_spec("x");
+        spec.toast_id = Some("a&b".to_string());
+        assert!(build_toast_xml(&spec).contains(r#" hint-toastId="a&amp;b""#));
+        spec.toast_id = None;
+        assert!(!build_toast_xml(&spec).contains("hint-toastId"));
+
+        let err = notify_error(input(""));
+        assert!(err.contains("toast_id must be 1 to"), "{err}");
+        let err = notify_error(input(&"x".repeat(MAX_GROUP_CHARS + 1)));
+        assert!(err.contains("toast_id must be 1 to"), "{err}");
+    }
+
+    #[test]
+    fn body_max_lines_is_clamped_with_a_warning() {
+        let notify_with = |lines: u32| {
+            let mut input: NotifyInput = serde_json::from_value(serde_json::json!({
+                "title": "T", "message": "m", "icon": "happy", "sound": "default",
+                "body_max_lines": lines
+            }))
+            .unwrap();
+            input.dry_run = true;
+            notify(input, &Config::default()).unwrap()
+        };
+        let outcome = notify_with(3);
+        assert_eq!(outcome.plan.unwrap().body_max_lines, Some(3));
+        assert!(outcome.warnings.is_empty());
+
+        for (asked, used) in [(0, 1), (MAX_TEXT_LINES + 6, MAX_TEXT_LINES)] {
+            let outcome = notify_with(asked);
+            assert_eq!(outcome.plan.unwrap().body_max_lines, Some(used));
+            assert_eq!(
+                outcome.warnings,
+                [format!(
+                    "body_max_lines must be 1 to {MAX_TEXT_LINES}; used {used} instead of {asked}."
+                )]
+            );
+        }
     }
 
-    if let Some(audio_src) = system_sound_to_audio_src(&input.sound) {
-        show_toast(
-            &input.title,
-            &input.message,
-            Some(icon_path.as_path()),
-            Some(audio_src),
-        )?;
-        return Ok(());
+    #[test]
+    fn every_body_line_gets_hint_max_lines() {
+        let mut spec = full_spec("x");
+        spec.body = vec!["first".to_string(), "second".to_string()];
+        spec.body_max_lines = Some(2);
+        let xml = build_toast_xml(&spec);
+        assert!(xml.contains(r#"<text hint-maxLines="2">first</text>"#));
+        assert!(xml.contains(r#"<text hint-maxLines="2">second</text>"#));
+        assert_eq!(xml.matches("hint-maxLines").count(), 2);
+        assert!(xml.contains(r#"<text placement="attribution">"#));
+
+        spec.body_max_lines = None;
+        assert!(!build_toast_xml(&spec).contains("hint-maxLines"));
     }
 
-    let sound_ids = list_sound_ids();
-    Err(anyhow::anyhow!(
-        "Sound not found: {}. Valid sound ids: {}. Use tools/list to refresh.",
-        input.sound,
-        format_ids(&sound_ids)
-    ))
-}
+    #[test]
+    fn strip_markdown_removes_common_syntax() {
+        assert_eq!(
+            strip_markdown("**Build** *passed* in `cargo`"),
+            "Build passed in cargo"
+        );
+        assert_eq!(strip_markdown("## Done\n# Title"), "Done\nTitle");
+        assert_eq!(
+            strip_markdown("See [the log](https://example.com/log) now"),
+            "See the log now"
+        );
+        assert_eq!(
+            strip_markdown("![diagram](a.png) attached"),
+            "diagram attached"
+        );
+        assert_eq!(
+            strip_markdown("Issue #12 and [x] stay"),
+            "Issue #12 and [x] stay"
+        );
+        assert_eq!(strip_markdown("2 * 3 = 6"), "2 * 3 = 6");
+        assert_eq!(
+            strip_markdown("Matched *.rs and a*b"),
+            "Matched *.rs and a*b"
+        );
+        assert_eq!(strip_markdown("#123 failed"), "#123 failed");
+        assert_eq!(strip_markdown("####### seven"), "####### seven");
+        assert_eq!(
+            strip_markdown("**Done:** *3 * 4* checks"),
+            "Done: 3 * 4 checks"
+        );
+        assert_eq!(strip_markdown("plain text"), "plain text");
+    }
 
-fn prepare_quiet_wav(path: &Path, volume: f32) -> Result<PathBuf> {
-    if !(0.0..=1.0).contains(&volume) {
-        return Ok(path.to_path_buf());
+    #[test]
+    fn markdown_is_only_stripped_when_asked() {
+        let input = |strip: bool| {
+            serde_json::json!({
+                "title": "T", "message": "**bold** `code`", "icon": "happy",
+                "sound": "default", "strip_markdown": strip
+            })
+        };
+        assert_eq!(plan(input(false)).body, ["**bold** `code`"]);
+        assert_eq!(plan(input(true)).body, ["bold code"]);
     }
 
-    let exe_dir = std::env::current_exe()
-        .ok()
-        .and_then(|p| p.parent().map(Path::to_path_buf))
-        .context("Failed to resolve exe directory")?;
-    let cache_dir = exe_dir.join("cache");
-    std::fs::create_dir_all(&cache_dir).context("Failed to create cache directory")?;
+    #[test]
+    fn toasts_in_one_thread_share_a_group_and_attribution() {
+        let message = |text: &str| {
+            serde_json::json!({
+                "title": "Agent", "message": text, "icon": "happy", "sound": "default",
+                "thread": {"id": "chat-42", "title": "Refactor chat"}
+            })
+        };
+        let first = plan(message("Starting"));
+        let second = plan(message("Done"));
+        assert_eq!(first.group.as_deref(), Some("chat-42"));
+        assert_eq!(first.group, second.group);
+        assert_eq!(first.attribution.as_deref(), Some("Refactor chat"));
+        assert_eq!(first.attribution, second.attribution);
 
-    let stem = path
-        .file_stem()
-        .and_then(|s| s.to_str())
-        .unwrap_or("sound");
-    let cache_name = format!("{stem}_vol70.wav");
-    let cache_path = cache_dir.join(cache_name);
+        let unthreaded = plan(serde_json::json!({
+            "title": "Agent", "message": "Alone", "icon": "happy", "sound": "default"
+        }));
+        assert_eq!(unthreaded.group, None);
+        assert_eq!(unthreaded.attribution, None);
+    }
 
-    if cache_path.exists() {
-        let src_time = std::fs::metadata(path)?.modified().ok();
-        let dst_time = std::fs::metadata(&cache_path)?.modified().ok();
-        if src_time.is_some() && dst_time.is_some() && dst_time >= src_time {
-            return Ok(cache_path);
+    #[test]
+    fn thread_ids_must_fit_a_toast_group() {
+        let thread = |id: String| {
+            serde_json::json!({
+                "title": "T", "message": "m", "icon": "happy", "sound": "default",
+                "thread": {"id": id, "title": "Chat"}
+            })
+        };
+        let err = notify_error(thread(String::new()));
+        assert!(err.contains("thread.id must be 1 to"), "{err}");
+        let err = notify_error(thread("x".repeat(MAX_GROUP_CHARS + 1)));
+        assert!(err.contains("thread.id must be 1 to"), "{err}");
+        assert!(plan(thread("x".repeat(MAX_GROUP_CHARS))).group.is_some());
+    }
+
+    #[test]
+    fn thread_titles_become_the_attribution_line() {
+        let mut spec = full_spec("x");
+        spec.attribution = Some("Build & test".to_string());
+        let xml = build_toast_xml(&spec);
+        assert!(xml.contains(r#"<text placement="attribution">Build &amp; test</text>"#));
+    }
+
+    #[test]
+    fn newlines_split_the_message_into_text_lines() {
+        let mut warnings = Vec::new();
+        let lines = split_body_lines("first\r\n\nsecond  \n", &mut warnings);
+        assert_eq!(lines, ["first", "second"]);
+        assert!(warnings.is_empty());
+
+        let lines = split_body_lines("\n\n", &mut warnings);
+        assert_eq!(lines, [""]);
+
+        let long: Vec<String> = (1..=MAX_BODY_LINES + 2)
+            .map(|n| format!("line {n}"))
+            .collect();
+        let lines = split_body_lines(&long.join("\n"), &mut warnings);
+        assert_eq!(lines, long[..MAX_BODY_LINES]);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("the rest were dropped"));
+    }
+
+    #[test]
+    fn summary_leads_and_detail_follows() {
+        let lines = summary_detail_lines(
+            Some("Build\n  passed".to_string()),
+            Some("All 42 tests\npassed in 3s".to_string()),
+        );
+        assert_eq!(lines, ["Build passed", "All 42 tests passed in 3s"]);
+
+        let long = "word ".repeat(MAX_SUMMARY_CHARS);
+        let lines = summary_detail_lines(Some(long), None);
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].ends_with("word…"), "{}", lines[0]);
+        assert_eq!(lines[0].chars().count(), MAX_SUMMARY_CHARS);
+
+        let lines = summary_detail_lines(None, Some("only detail".to_string()));
+        assert_eq!(lines, ["only detail"]);
+        assert_eq!(summary_detail_lines(Some(" ".to_string()), None), [""]);
+    }
+
+    #[test]
+    fn summary_and_detail_replace_the_message() {
+        let plan = plan(serde_json::json!({
+            "title": "CI", "summary": "**Green**", "detail": "Every job passed",
+            "icon": "happy", "sound": "default", "strip_markdown": true
+        }));
+        assert_eq!(plan.body, ["Green", "Every job passed"]);
+
+        let err = notify_error(serde_json::json!({
+            "title": "CI", "message": "m", "summary": "s", "icon": "happy", "sound": "default"
+        }));
+        assert!(err.contains("either message or summary/detail"), "{err}");
+    }
+
+    #[test]
+    fn each_body_line_is_its_own_text_element() {
+        let mut spec = full_spec("x");
+        spec.body = vec!["one".to_string(), "two & three".to_string()];
+        spec.attribution = None;
+        let xml = build_toast_xml(&spec);
+        assert!(xml.contains(">one</text>"));
+        assert!(xml.contains(">two &amp; three</text>"));
+        assert_eq!(xml.matches("<text").count(), 3);
+    }
+
+    #[test]
+    fn icon_uris_percent_encode_each_path_segment() {
+        let uri = icon_uri(Path::new("C:\\Program Files\\Toast #1\\icons\\happy.png"));
+        assert_eq!(
+            uri.as_deref(),
+            Some("file:///C:/Program%20Files/Toast%20%231/icons/happy.png")
+        );
+        let uri = icon_uri(Path::new("C:\\Users\\zoë\\100%\\icon.png"));
+        assert_eq!(
+            uri.as_deref(),
+            Some("file:///C:/Users/zo%C3%AB/100%25/icon.png")
+        );
+        let xml = build_toast_xml(&full_spec("x"));
+        assert!(xml.contains("file:///C:/Assets%20%26%20Icons/%C3%A9t%C3%A9%20%231.png"));
+    }
+
+    fn full_spec(text: &str) -> ToastSpec {
+        ToastSpec {
+            title: text.to_string(),
+            body: vec![text.to_string(); MAX_BODY_LINES],
+            icon_path: Some(PathBuf::from("C:\\Assets & Icons\\été #1.png")),
+            audio_src: Some("ms-winsoundevent:Notification.Default"),
+            suppress_popup: false,
+            tag: Some("tag".to_string()),
+            expires_in: None,
+            launch: Some(text.to_string()),
+            progress: Some(ProgressInput {
+                value: Some(ProgressValue::Fraction(0.5)),
+                percent: None,
+                status: "Working".to_string(),
+            }),
+            group: None,
+            attribution: Some(text.to_string()),
+            display_timestamp: Some("2026-10-16T12:00:00Z".to_string()),
+            body_max_lines: Some(MAX_TEXT_LINES),
+            toast_id: Some(text.to_string()),
+            correlation_id: None,
+            load_xml: false,
         }
     }
 
-    let mut data = std::fs::read(path).context("Failed to read wav file")?;
-    if data.len() < 12 || &data[0..4] != b"RIFF" || &data[8..12] != b"WAVE" {
-        return Ok(path.to_path_buf());
+    #[test]
+    fn benchmark_toasts_use_the_template_unless_load_xml_is_set() {
+        let mut input = BenchmarkInput {
+            count: 3,
+            tag: "bench".to_string(),
+            load_xml: false,
+        };
+        let icon = Some(PathBuf::from("icons/happy.png"));
+        assert!(fits_toast_template(&benchmark_spec(
+            &input,
+            1,
+            icon.clone()
+        )));
+        assert!(!fits_toast_template(&benchmark_spec(&input, 1, None)));
+        input.load_xml = true;
+        assert!(!fits_toast_template(&benchmark_spec(&input, 1, icon)));
+        assert!(!fits_toast_template(&full_spec("text")));
+    }
+
+    #[test]
+    fn toast_xml_len_is_an_upper_bound() {
+        for text in ["", "plain", "<&\"'>", "ünïcødé ✓"] {
+            let spec = full_spec(text);
+            assert!(
+                toast_xml_len(&spec) >= build_toast_xml(&spec).len(),
+                "{text:?}"
+            );
+        }
+        let mut bare = full_spec("x");
+        bare.body = vec![String::new()];
+        bare.icon_path = None;
+        bare.launch = None;
+        bare.progress = None;
+        bare.attribution = None;
+        bare.display_timestamp = None;
+        bare.body_max_lines = None;
+        bare.toast_id = None;
+        assert!(toast_xml_len(&bare) >= build_toast_xml(&bare).len());
+    }
+
+    #[test]
+    fn oversized_toasts_are_rejected_before_delivery() {
+        let input = |message: String| {
+            serde_json::json!({
+                "title": "Big", "message": message, "icon": "happy", "sound": "default"
+            })
+        };
+        let err = notify_error(input("<&>".repeat(1_000)));
+        assert!(err.contains("over the 5120-byte limit"), "{err}");
+
+        let mut fits: NotifyInput = serde_json::from_value(input("x".repeat(4_000))).unwrap();
+        fits.dry_run = true;
+        notify(fits, &Config::default()).unwrap();
+    }
+
+    #[test]
+    fn timings_line_carries_the_correlation_id() {
+        let _logger = log::tests::lock_logger();
+        let log_file = temp_dir("timings").join("toastmcp.log");
+        log::init(Some(&log_file), LogLevel::Trace).unwrap();
+        log_timings(&NotifyTimings::default(), Some("run-42"));
+        log_timings(&NotifyTimings::default(), None);
+        log::init(None, LogLevel::Info).unwrap();
+
+        let lines: Vec<Value> = std::fs::read_to_string(&log_file)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["msg"], "notify timings");
+        assert_eq!(lines[0]["correlation_id"], "run-42");
+        assert!(lines[1].get("correlation_id").is_none());
+        std::fs::remove_dir_all(log_file.parent().unwrap()).unwrap();
     }
 
-    let mut cursor = 12;
-    let mut fmt_chunk: Option<(u16, u16, u16)> = None;
-    let mut data_chunk: Option<(usize, usize)> = None;
+    #[test]
+    fn every_phase_is_timed_and_logged() {
+        let phases = [
+            "validate_ms",
+            "prepare_sound_ms",
+            "play_sound_ms",
+            "com_init_ms",
+            "shortcut_ms",
+            "show_ms",
+            "total_ms",
+        ];
+        let serialized = serde_json::to_value(NotifyTimings::default()).unwrap();
+        let mut keys: Vec<&str> = serialized
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .collect();
+        keys.sort_unstable();
+        let mut expected = phases;
+        expected.sort_unstable();
+        assert_eq!(keys, expected);
 
-    while cursor + 8 <= data.len() {
-        let chunk_id = &data[cursor..cursor + 4];
-        let chunk_size = u32::from_le_bytes([
-            data[cursor + 4],
-            data[cursor + 5],
-            data[cursor + 6],
-            data[cursor + 7],
-        ]) as usize;
-        let chunk_start = cursor + 8;
-        let chunk_end = chunk_start.saturating_add(chunk_size);
-        if chunk_end > data.len() {
-            break;
+        let _logger = log::tests::lock_logger();
+        let log_file = temp_dir("phase-timings").join("toastmcp.log");
+        log::init(Some(&log_file), LogLevel::Trace).unwrap();
+        let input: NotifyInput = serde_json::from_value(serde_json::json!({
+            "title": "Timed", "message": "m", "icon": "happy", "sound": "default",
+            "mode": "log_only", "verbose": true
+        }))
+        .unwrap();
+        let shown = std::cell::Cell::new(false);
+        let show = |spec: &ToastSpec, _: &Config, timings: &mut NotifyTimings| {
+            assert!(spec.suppress_popup);
+            timings.show_ms = 1.0;
+            shown.set(true);
+            Ok(())
+        };
+        let outcome = notify_with(input, &Config::default(), &show).unwrap();
+        assert!(shown.get());
+        assert_eq!(outcome.timings.unwrap().show_ms, 1.0);
+        log::init(None, LogLevel::Info).unwrap();
+
+        let line: Value = std::fs::read_to_string(&log_file)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .find(|line: &Value| line["msg"] == "notify timings")
+            .unwrap();
+        for phase in phases {
+            assert!(line[phase].as_f64().unwrap() >= 0.0, "{phase}: {line}");
         }
+        assert!(line["total_ms"].as_f64() >= line["validate_ms"].as_f64());
+        assert_eq!(line["show_ms"], 1.0);
+        std::fs::remove_dir_all(log_file.parent().unwrap()).unwrap();
+    }
+
+    fn recording_debouncer() -> (Arc<ProgressDebouncer<u32>>, std::sync::mpsc::Receiver<u32>) {
+        let (pushed, receiver) = std::sync::mpsc::channel();
+        let pushed = Mutex::new(pushed);
+        let debouncer = ProgressDebouncer::new(move |value| {
+            pushed.lock().unwrap().send(value).unwrap();
+            Ok(())
+        });
+        (debouncer, receiver)
+    }
+
+    #[test]
+    fn rapid_progress_updates_coalesce_to_the_latest() {
+        let (debouncer, pushed) = recording_debouncer();
+        let interval = std::time::Duration::from_millis(50);
+        let submit = |key: &str, value| debouncer.submit(key.to_string(), value, interval).unwrap();
+
+        assert!(submit("build", 1));
+        assert!(!submit("build", 2));
+        assert!(!submit("build", 3));
+        assert!(submit("tests", 10));
+        assert!(!submit("build", 4));
 
-        if chunk_id == b"fmt " && chunk_size >= 16 {
-            let audio_format = u16::from_le_bytes([data[chunk_start], data[chunk_start + 1]]);
-            let channels = u16::from_le_bytes([data[chunk_start + 2], data[chunk_start + 3]]);
-            let bits_per_sample = u16::from_le_bytes([
-                data[chunk_start + 14],
-                data[chunk_start + 15],
-            ]);
-            fmt_chunk = Some((audio_format, channels, bits_per_sample));
-        } else if chunk_id == b"data" {
-            data_chunk = Some((chunk_start, chunk_size));
+        let timeout = std::time::Duration::from_secs(5);
+        let order: Vec<u32> = (0..3)
+            .map(|_| pushed.recv_timeout(timeout).unwrap())
+            .collect();
+        assert_eq!(order, [1, 10, 4]);
+        assert!(pushed.recv_timeout(interval * 3).is_err());
+
+        // Once the interval has passed quietly, the next update goes straight out.
+        assert!(submit("build", 5));
+        assert_eq!(pushed.recv_timeout(timeout).unwrap(), 5);
+    }
+
+    #[test]
+    fn a_zero_interval_pushes_every_progress_update() {
+        let (debouncer, pushed) = recording_debouncer();
+        for value in 1..=3 {
+            assert!(
+                debouncer
+                    .submit("build".to_string(), value, std::time::Duration::ZERO)
+                    .unwrap()
+            );
         }
+        assert_eq!(pushed.try_iter().collect::<Vec<_>>(), [1, 2, 3]);
+    }
 
-        cursor = chunk_end + (chunk_size % 2);
+    #[test]
+    fn immediate_progress_push_errors_are_returned() {
+        let debouncer: Arc<ProgressDebouncer<u32>> =
+            ProgressDebouncer::new(|_| Err(anyhow::anyhow!("no toast with that tag")));
+        let err = debouncer
+            .submit("gone".to_string(), 1, std::time::Duration::from_millis(50))
+            .unwrap_err();
+        assert!(err.to_string().contains("no toast with that tag"));
     }
 
-    let Some((audio_format, _channels, bits_per_sample)) = fmt_chunk else {
-        return Ok(path.to_path_buf());
-    };
-    let Some((data_start, data_size)) = data_chunk else {
-        return Ok(path.to_path_buf());
-    };
-    if audio_format != 1 || bits_per_sample != 16 {
-        return Ok(path.to_path_buf());
+    #[test]
+    fn queued_sounds_play_in_order() {
+        let (played, receiver) = std::sync::mpsc::channel();
+        let queue = SoundQueue::spawn(move |path| played.send(path.to_path_buf()).unwrap());
+        let paths = ["first.wav", "second.wav", "third.wav"].map(PathBuf::from);
+        for path in &paths {
+            assert!(queue.push(path, 8));
+        }
+        let order: Vec<PathBuf> = receiver.iter().take(paths.len()).collect();
+        assert_eq!(order, paths);
     }
 
-    let data_end = data_start.saturating_add(data_size).min(data.len());
-    let mut i = data_start;
-    while i + 1 < data_end {
-        let sample = i16::from_le_bytes([data[i], data[i + 1]]);
-        let scaled = (sample as f32 * volume)
-            .round()
-            .clamp(i16::MIN as f32, i16::MAX as f32) as i16;
-        let bytes = scaled.to_le_bytes();
-        data[i] = bytes[0];
-        data[i + 1] = bytes[1];
-        i += 2;
+    #[test]
+    fn full_sound_queues_skip_new_sounds() {
+        let (played, receiver) = std::sync::mpsc::channel();
+        let (release, gate) = std::sync::mpsc::channel::<()>();
+        let gate = Mutex::new(gate);
+        let queue = SoundQueue::spawn(move |path| {
+            gate.lock().unwrap().recv().unwrap();
+            played.send(path.to_path_buf()).unwrap();
+        });
+
+        assert!(queue.push(Path::new("a.wav"), 2));
+        assert!(queue.push(Path::new("b.wav"), 2));
+        assert!(!queue.push(Path::new("c.wav"), 2));
+        release.send(()).unwrap();
+        assert_eq!(receiver.recv().unwrap(), Path::new("a.wav"));
+        // The worker frees the slot just after playing returns.
+        while queue.held().len() > 1 {
+            std::thread::yield_now();
+        }
+        assert!(queue.push(Path::new("d.wav"), 2));
+        release.send(()).unwrap();
+        release.send(()).unwrap();
+        let rest: Vec<PathBuf> = receiver.iter().take(2).collect();
+        assert_eq!(rest, [PathBuf::from("b.wav"), PathBuf::from("d.wav")]);
     }
 
-    std::fs::write(&cache_path, &data).context("Failed to write cached wav")?;
-    Ok(cache_path)
-}
+    #[test]
+    fn queued_sounds_are_held_until_they_finish() {
+        let (release, gate) = std::sync::mpsc::channel::<()>();
+        let gate = Mutex::new(gate);
+        let queue = SoundQueue::spawn(move |_| gate.lock().unwrap().recv().unwrap());
+        let (first, second) = (PathBuf::from("first.wav"), PathBuf::from("second.wav"));
+        assert!(queue.push(&first, 4));
+        assert!(queue.push(&second, 4));
+        assert_eq!(queue.held(), [first, second.clone()]);
 
-fn resolve_sound(sound_id: &str) -> Result<PathBuf> {
-    let file_name = format!("{sound_id}.wav");
-    resolve_asset("sounds", &file_name)
-}
+        release.send(()).unwrap();
+        while queue.held().len() > 1 {
+            std::thread::yield_now();
+        }
+        assert_eq!(queue.held(), [second]);
+        release.send(()).unwrap();
+    }
 
-fn find_sound_path(sound_id: &str) -> Option<PathBuf> {
-    resolve_sound(sound_id).ok()
-}
+    #[test]
+    fn cache_cleanup_spares_sounds_still_in_use() {
+        let dir = temp_dir("cleanup");
+        let names = [
+            "playing_vol50.wav",
+            "queued_vol50.wav",
+            "done_vol50.wav",
+            "logo_bg112233.png",
+        ];
+        for name in names {
+            std::fs::write(dir.join(name), b"x").unwrap();
+        }
+        let in_use = [dir.join("playing_vol50.wav"), dir.join("queued_vol50.wav")];
+        let remaining = || {
+            let mut names: Vec<String> = std::fs::read_dir(&dir)
+                .unwrap()
+                .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+                .collect();
+            names.sort();
+            names
+        };
 
-fn resolve_icon(icon_id: &str) -> Result<PathBuf> {
-    let file_name = format!("{icon_id}.png");
-    resolve_asset("icons", &file_name)
-}
+        assert_eq!(cleanup_dir(&dir, false, &in_use), 1);
+        assert_eq!(
+            remaining(),
+            ["done_vol50.wav", "playing_vol50.wav", "queued_vol50.wav"]
+        );
+        assert_eq!(cleanup_dir(&dir, true, &in_use), 1);
+        assert_eq!(remaining(), ["playing_vol50.wav", "queued_vol50.wav"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 
-fn resolve_asset(folder: &str, file_name: &str) -> Result<PathBuf> {
-    let exe_dir = std::env::current_exe()
-        .ok()
-        .and_then(|path| path.parent().map(Path::to_path_buf));
+    #[test]
+    fn log_only_rejects_audio_loop() {
+        let error = notify_error(serde_json::json!({
+            "title": "Build",
+            "message": "Done",
+            "icon": "info",
+            "sound": "done",
+            "mode": "log_only",
+            "audio_loop": true
+        }));
+        assert!(error.contains("audio_loop"), "{error}");
+    }
 
-    if let Some(dir) = exe_dir {
-        let candidate = dir.join(folder).join(file_name);
-        if candidate.exists() {
-            return Ok(candidate);
+    #[test]
+    fn audio_loop_rejects_system_sounds() {
+        for sound_kind in ["system", "auto"] {
+            let error = notify_error(serde_json::json!({
+                "title": "T", "message": "m", "icon": "happy",
+                "sound": "alarm", "sound_kind": sound_kind, "audio_loop": true
+            }));
+            assert!(error.contains("audio_loop needs a WAV file"), "{error}");
         }
+        let fixture = crate::assets::tests::SoundFixture::new("loop-wav");
+        let plan = plan(serde_json::json!({
+            "title": "T", "message": "m", "icon": "happy",
+            "sound": "loop-wav", "audio_loop": true
+        }));
+        assert_eq!(plan.loop_secs, Some(DEFAULT_LOOP_SECS.into()));
+        drop(fixture);
     }
 
-    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
-    let candidate = manifest_dir.join(folder).join(file_name);
-    if candidate.exists() {
-        return Ok(candidate);
+    #[test]
+    fn sound_generations_stop_only_the_current_loop() {
+        let sounds = SoundGenerations::new();
+        let (first, was_looping) = sounds.advance();
+        assert!(!was_looping);
+        sounds.start_loop(first);
+        // The loop's timer fires while it is still the latest sound.
+        assert!(sounds.end_loop(first));
+        assert!(!sounds.end_loop(first));
+
+        let (looped, _) = sounds.advance();
+        sounds.start_loop(looped);
+        // The next notify stops the loop itself, whatever it plays...
+        let (next, was_looping) = sounds.advance();
+        assert!(was_looping);
+        assert!(next > looped);
+        // ...so the stale timer must leave the newer sound alone.
+        assert!(!sounds.end_loop(looped));
+        assert!(!sounds.advance().1);
+
+        let (replaced, _) = sounds.advance();
+        sounds.start_loop(replaced);
+        let (newer, _) = sounds.advance();
+        sounds.start_loop(newer);
+        assert!(!sounds.end_loop(replaced));
+        assert!(sounds.end_loop(newer));
     }
 
-    Err(anyhow::anyhow!(
-        "Missing asset: {}/{}",
-        folder,
-        file_name
-    ))
-}
+    fn tone(freq: u32, ms: u32) -> BeepTone {
+        BeepTone { freq, ms }
+    }
 
-fn format_ids(ids: &[String]) -> String {
-    if ids.is_empty() {
-        return "none (add assets to the folder)".to_string();
+    fn beep_error(tones: &[BeepTone]) -> String {
+        validate_beep_sequence(tones).unwrap_err().to_string()
     }
-    ids.join(", ")
-}
 
-#[cfg(windows)]
-fn play_sound(path: &Path) -> Result<()> {
-    use std::ffi::OsStr;
-    use std::os::windows::ffi::OsStrExt;
+    #[test]
+    fn beep_frequencies_and_lengths_are_bounded() {
+        validate_beep_sequence(&[
+            tone(MIN_BEEP_FREQ, 1),
+            tone(MAX_BEEP_FREQ, MAX_BEEP_TONE_MS),
+        ])
+        .unwrap();
+        for freq in [0, MIN_BEEP_FREQ - 1, MAX_BEEP_FREQ + 1, u32::MAX] {
+            let err = beep_error(&[tone(freq, 100)]);
+            assert_eq!(
+                err,
+                format!("beep freq must be between 37 and 32767 Hz (got {freq})")
+            );
+        }
+        for ms in [0, MAX_BEEP_TONE_MS + 1] {
+            let err = beep_error(&[tone(440, 100), tone(440, ms)]);
+            assert_eq!(
+                err,
+                format!("beep ms must be between 1 and 2000 (got {ms})")
+            );
+        }
+    }
 
-    use windows::Win32::Media::Audio::{
-        PlaySoundW, SND_ASYNC, SND_FILENAME, SND_NODEFAULT,
-    };
+    #[test]
+    fn beep_sequences_cap_the_tone_count() {
+        assert_eq!(
+            beep_error(&[]),
+            "beep_sequence must have 1 to 16 tones (got 0)"
+        );
+        validate_beep_sequence(&[tone(440, 1); MAX_BEEP_TONES]).unwrap();
+        assert_eq!(
+            beep_error(&[tone(440, 1); MAX_BEEP_TONES + 1]),
+            "beep_sequence must have 1 to 16 tones (got 17)"
+        );
+    }
 
-    let wide: Vec<u16> = OsStr::new(path)
-        .encode_wide()
-        .chain(std::iter::once(0))
-        .collect();
+    #[test]
+    fn beep_sequences_cap_the_total_duration() {
+        let at_limit = [tone(440, 2_000), tone(440, 2_000), tone(440, 1_000)];
+        assert_eq!(beep_sequence_ms(&at_limit), MAX_BEEP_TOTAL_MS);
+        validate_beep_sequence(&at_limit).unwrap();
+        let over = [tone(440, 2_000), tone(440, 2_000), tone(440, 1_001)];
+        assert_eq!(
+            beep_error(&over),
+            "beep_sequence lasts 5001 ms; the limit is 5000 ms"
+        );
 
-    unsafe {
-        PlaySoundW(
-            windows::core::PCWSTR(wide.as_ptr()),
-            None,
-            SND_FILENAME | SND_ASYNC | SND_NODEFAULT,
-        )
-        .ok()
-        .context("PlaySoundW failed")?;
+        let plan = plan(serde_json::json!({
+            "title": "T", "message": "m", "icon": "happy",
+            "beep_sequence": [{"freq": 440, "ms": 200}, {"freq": 880, "ms": 300}]
+        }));
+        assert!(matches!(
+            plan.sound,
+            PlannedSound::BeepSequence {
+                tones: 2,
+                total_ms: 500
+            }
+        ));
     }
-    Ok(())
-}
 
-#[cfg(not(windows))]
-fn play_sound(_path: &Path) -> Result<()> {
-    Err(anyhow::anyhow!("Sound playback is only implemented on Windows"))
-}
+    #[test]
+    fn beep_sequence_length_saturates() {
+        assert_eq!(beep_sequence_ms(&[]), 0);
+        let huge = [tone(440, u32::MAX), tone(440, u32::MAX), tone(440, 1)];
+        assert_eq!(beep_sequence_ms(&huge), u32::MAX);
+    }
 
-#[cfg(windows)]
-fn show_toast(
-    title: &str,
-    message: &str,
-    icon_path: Option<&Path>,
-    audio_src: Option<&'static str>,
-) -> Result<()> {
-    use windows::Data::Xml::Dom::XmlDocument;
-    use windows::UI::Notifications::{ToastNotification, ToastNotificationManager};
-    use windows::Win32::System::Com::{CoInitializeEx, COINIT_APARTMENTTHREADED};
-    use windows::Win32::UI::Shell::SetCurrentProcessExplicitAppUserModelID;
-    use windows::core::HSTRING;
+    #[test]
+    fn log_only_rejects_beep_sequence() {
+        let error = notify_error(serde_json::json!({
+            "title": "Build",
+            "message": "Done",
+            "icon": "info",
+            "mode": "log_only",
+            "beep_sequence": [{"freq": 880, "ms": 100}]
+        }));
+        assert!(error.contains("beep_sequence"), "{error}");
+    }
 
-    unsafe {
-        CoInitializeEx(None, COINIT_APARTMENTTHREADED)
-            .ok()
-            .context("CoInitializeEx failed")?;
+    fn prepare(sound: &str, config: &Config) -> String {
+        let input = PrepareSoundInput {
+            sound: sound.to_string(),
+            volume: None,
+            force: false,
+        };
+        format!("{:#}", prepare_sound(input, config).unwrap_err())
     }
 
-    let app_id = HSTRING::from("ToastMCP");
-    unsafe {
-        SetCurrentProcessExplicitAppUserModelID(&app_id)
-            .context("SetCurrentProcessExplicitAppUserModelID failed")?;
+    #[test]
+    fn prepare_sound_rejects_path_traversal() {
+        let config = Config::default();
+        for sound in [
+            "../config",
+            "..\\secret",
+            "sub/beep",
+            "C:beep",
+            ".hidden",
+            ".",
+            "..",
+            "beep\0",
+            "",
+        ] {
+            assert!(
+                prepare(sound, &config).contains("Invalid asset id"),
+                "{sound}"
+            );
+        }
     }
-    ensure_start_menu_shortcut("ToastMCP")?;
 
-    let image_fragment = icon_path
-        .and_then(|path| path.to_str())
-        .map(|path| format!(r#"<image placement="appLogoOverride" src="file:///{path}"/>"#))
-        .unwrap_or_default();
+    #[test]
+    fn locating_and_renaming_write_no_assets() {
+        let exe_icons = std::env::current_exe()
+            .unwrap()
+            .parent()
+            .unwrap()
+            .join("icons");
+        let had_icons = exe_icons.exists();
+        let config = Config::default();
+        assert!(!config.read_only);
 
-    let audio_fragment = audio_src
-        .map(|src| format!(r#"<audio src="{src}"/>"#))
-        .unwrap_or_else(|| "<audio silent=\"true\"/>".to_string());
+        let located = locate_asset(
+            ResolveAssetInput {
+                kind: AssetKind::Icon,
+                id: "no-such-icon".into(),
+            },
+            &config,
+        )
+        .unwrap();
+        assert_eq!(located.path, None);
+        let located = locate_asset(
+            ResolveAssetInput {
+                kind: AssetKind::Icon,
+                id: "happy".into(),
+            },
+            &config,
+        )
+        .unwrap();
+        assert!(located.path.unwrap().is_file());
 
-    let toast_xml = format!(
-        r#"<toast>
-  <visual>
-    <binding template="ToastGeneric">
-      <text>{}</text>
-      <text>{}</text>
-      {}
-    </binding>
-  </visual>
-  {}
-</toast>"#,
-        xml_escape(title),
-        xml_escape(message),
-        image_fragment,
-        audio_fragment
-    );
+        let rename = RenameAssetInput {
+            kind: AssetKind::Icon,
+            from: "no-such-icon".into(),
+            to: "still-missing".into(),
+        };
+        let err = rename_paths(&rename, &config).unwrap_err().to_string();
+        assert_eq!(err, "Missing asset: icons/no-such-icon.png");
+        assert_eq!(exe_icons.exists(), had_icons);
+    }
 
-    let document = XmlDocument::new()?;
-    document.LoadXml(&HSTRING::from(toast_xml))?;
-    let toast = ToastNotification::CreateToastNotification(&document)?;
-    let notifier = ToastNotificationManager::CreateToastNotifierWithId(&app_id)?;
-    notifier.Show(&toast)?;
-    Ok(())
-}
+    #[test]
+    fn listed_ids_with_spaces_are_valid_asset_ids() {
+        for id in ["Level Up", "alert (2)", "caf\u{e9}", "v1.2"] {
+            assert!(validate_asset_id(id).is_ok(), "{id}");
+        }
 
-#[cfg(not(windows))]
-fn show_toast(
-    _title: &str,
-    _message: &str,
-    _icon_path: Option<&Path>,
-    _audio_src: Option<&'static str>,
-) -> Result<()> {
-    Err(anyhow::anyhow!("Toast notifications are only implemented on Windows"))
-}
+        let fixture = crate::assets::tests::SoundFixture::new("Level Up");
+        let config = Config::default();
+        assert!(list_sound_ids(&config).contains(&"Level Up".to_string()));
+        let prepared = prepare_sound(
+            PrepareSoundInput {
+                sound: "Level Up".to_string(),
+                volume: Some(0.41),
+                force: false,
+            },
+            &Config {
+                read_only: true,
+                ..Config::default()
+            },
+        )
+        .unwrap();
+        assert!(
+            prepared.path.ends_with("Level Up_vol41.wav"),
+            "{prepared:?}"
+        );
 
-fn xml_escape(value: &str) -> String {
-    value
-        .replace('&', "&amp;")
-        .replace('<', "&lt;")
-        .replace('>', "&gt;")
-        .replace('"', "&quot;")
-        .replace('\'', "&apos;")
-}
+        let located = locate_asset(
+            ResolveAssetInput {
+                kind: AssetKind::Sound,
+                id: "Level Up".to_string(),
+            },
+            &config,
+        )
+        .unwrap();
+        assert_eq!(located.path.as_deref(), Some(fixture.0.as_path()));
 
-fn system_sound_to_audio_src(sound_id: &str) -> Option<&'static str> {
-    match sound_id {
-        "default" => Some("ms-winsoundevent:Notification.Default"),
-        "im" => Some("ms-winsoundevent:Notification.IM"),
-        "mail" => Some("ms-winsoundevent:Notification.Mail"),
-        "reminder" => Some("ms-winsoundevent:Notification.Reminder"),
-        "sms" => Some("ms-winsoundevent:Notification.SMS"),
-        "alarm" => Some("ms-winsoundevent:Notification.Alarm"),
-        "incoming_call" => Some("ms-winsoundevent:Notification.IncomingCall"),
-        _ => None,
+        let (source, target) = rename_paths(
+            &RenameAssetInput {
+                kind: AssetKind::Sound,
+                from: "Level Up".to_string(),
+                to: "alert (2)".to_string(),
+            },
+            &config,
+        )
+        .unwrap();
+        assert_eq!(source, fixture.0);
+        assert_eq!(target, fixture.0.with_file_name("alert (2).wav"));
+        drop(fixture);
     }
-}
 
+    #[test]
+    fn preview_sound_rejects_path_traversal() {
+        let input = PreviewSoundInput {
+            sound: "../../Windows/Media/tada".to_string(),
+            volume: None,
+        };
+        let error = format!(
+            "{:#}",
+            preview_sound(input, &Config::default()).unwrap_err()
+        );
+        assert!(error.contains("Invalid asset id"), "{error}");
+    }
 
-#[cfg(windows)]
-fn ensure_start_menu_shortcut(app_id: &str) -> Result<()> {
-    use std::ffi::OsStr;
-    use std::os::windows::ffi::OsStrExt;
+    #[test]
+    fn prepare_sound_respects_the_allow_list() {
+        let config = Config {
+            denied_sounds: vec!["beep".to_string()],
+            ..Config::default()
+        };
+        assert!(prepare("beep", &config).contains("not allowed"));
+        let config = Config {
+            allowed_sounds: Some(vec!["chime*".to_string()]),
+            ..Config::default()
+        };
+        assert!(prepare("beep", &config).contains("not allowed"));
+    }
 
-    use windows::Win32::System::Com::{CoCreateInstance, IPersistFile, CLSCTX_INPROC_SERVER};
-    use windows::Win32::UI::Shell::IShellLinkW;
-    use windows::Win32::UI::Shell::PropertiesSystem::IPropertyStore;
-    use windows::Win32::Storage::EnhancedStorage::PKEY_AppUserModel_ID;
-    use windows::core::{Interface, PROPVARIANT};
+    fn progress(value: Value) -> Result<ProgressValue> {
+        serde_json::from_value::<ProgressInput>(value)
+            .map_err(anyhow::Error::from)
+            .and_then(|progress| progress.amount())
+    }
 
-    let appdata = std::env::var("APPDATA").context("APPDATA not set")?;
-    let shortcut_path = std::path::PathBuf::from(appdata)
-        .join("Microsoft\\Windows\\Start Menu\\Programs\\ToastMCP.lnk");
+    #[test]
+    fn progress_value_is_always_a_fraction() {
+        use serde_json::json;
+        assert_eq!(
+            progress(json!({"value": 0})).unwrap(),
+            ProgressValue::Fraction(0.0)
+        );
+        assert_eq!(
+            progress(json!({"value": 1})).unwrap(),
+            ProgressValue::Fraction(1.0)
+        );
+        assert_eq!(
+            progress(json!({"value": 1.0})).unwrap(),
+            ProgressValue::Fraction(1.0)
+        );
+        assert_eq!(
+            progress(json!({"value": 0.25})).unwrap(),
+            ProgressValue::Fraction(0.25)
+        );
+        assert!(progress(json!({"value": 1.01})).is_err());
+        assert!(progress(json!({"value": -0.1})).is_err());
+        assert!(progress(json!({"value": 50})).is_err());
+    }
 
-    if shortcut_path.exists() {
-        let _ = std::fs::remove_file(&shortcut_path);
+    #[test]
+    fn progress_percent_is_normalized() {
+        use serde_json::json;
+        assert_eq!(
+            progress(json!({"percent": 0})).unwrap(),
+            ProgressValue::Fraction(0.0)
+        );
+        assert_eq!(
+            progress(json!({"percent": 1})).unwrap(),
+            ProgressValue::Fraction(0.01)
+        );
+        assert_eq!(
+            progress(json!({"percent": 100})).unwrap(),
+            ProgressValue::Fraction(1.0)
+        );
+        assert!(progress(json!({"percent": 101})).is_err());
+        assert!(progress(json!({"percent": -1})).is_err());
+        assert!(progress(json!({"percent": 0.5})).is_err());
     }
 
-    let exe_path = std::env::current_exe().context("Failed to resolve current exe")?;
-    let icon_path = exe_path
-        .parent()
-        .map(|dir| dir.join("res\\ToastMCP.ico"))
-        .filter(|path| path.exists());
+    #[test]
+    fn progress_indeterminate_and_bad_input() {
+        use serde_json::json;
+        let indeterminate = progress(json!({"value": "indeterminate"})).unwrap();
+        assert_eq!(indeterminate.binding(), "indeterminate");
+        assert!(progress(json!({"value": "half"})).is_err());
+        assert!(progress(json!({"value": 0.5, "percent": 50})).is_err());
+        assert!(progress(json!({"status": "Compiling"})).is_err());
+        assert_eq!(ProgressValue::Fraction(1.0).binding(), "1");
+        assert_eq!(ProgressValue::Fraction(0.4).binding(), "0.4");
+    }
 
-    let link: IShellLinkW = unsafe { CoCreateInstance(&windows::Win32::UI::Shell::ShellLink, None, CLSCTX_INPROC_SERVER)? };
-    let exe_wide: Vec<u16> = OsStr::new(&exe_path)
-        .encode_wide()
-        .chain(std::iter::once(0))
-        .collect();
-    unsafe {
-        link.SetPath(windows::core::PCWSTR(exe_wide.as_ptr()))
-            .context("SetPath failed")?;
-        if let Some(icon_path) = icon_path.as_ref() {
-            let icon_wide: Vec<u16> = OsStr::new(icon_path)
-                .encode_wide()
-                .chain(std::iter::once(0))
-                .collect();
-            link.SetIconLocation(windows::core::PCWSTR(icon_wide.as_ptr()), 0)
-                .context("SetIconLocation failed")?;
+    #[test]
+    fn volume_cache_stem_matches_the_exact_sound() {
+        assert_eq!(volume_cache_stem(Path::new("beep_vol70.wav")), Some("beep"));
+        assert_eq!(
+            volume_cache_stem(Path::new("cache/beep_vol2_vol70.wav")),
+            Some("beep_vol2")
+        );
+        assert_eq!(volume_cache_stem(Path::new("beep_volume.wav")), None);
+        assert_eq!(volume_cache_stem(Path::new("beep_vol.wav")), None);
+        assert_eq!(volume_cache_stem(Path::new("beep_vol70.png")), None);
+        assert!(is_volume_cache_file(Path::new("done_vol100.wav")));
+        assert!(!is_volume_cache_file(Path::new("done_bg112233.png")));
+    }
+
+    #[test]
+    fn hex_colors_parse_to_rgb() {
+        assert_eq!(parse_hex_color("#a1B2c3").unwrap(), [0xa1, 0xb2, 0xc3]);
+        assert_eq!(parse_hex_color("#000000").unwrap(), [0, 0, 0]);
+        for color in [
+            "a1b2c3", "#a1b2c", "#a1b2c3d", "", "#", "#g1b2c3", "#+1b2c3", "#é1b2c",
+        ] {
+            let err = parse_hex_color(color).unwrap_err().to_string();
+            assert!(
+                err.contains("icon_bg must look like \"#RRGGBB\""),
+                "{color}: {err}"
+            );
         }
     }
 
-    unsafe {
-        let propvariant = PROPVARIANT::from(app_id);
-        let store = link.cast::<IPropertyStore>()?;
-        store
-            .SetValue(&PKEY_AppUserModel_ID, &propvariant)
-            .context("SetValue AppUserModelID failed")?;
-        store.Commit().context("Commit AppUserModelID failed")?;
+    #[test]
+    fn background_cache_stem_matches_the_exact_icon() {
+        assert_eq!(
+            background_cache_stem(Path::new("done_bg1a2b3c.png")),
+            Some("done")
+        );
+        assert_eq!(
+            background_cache_stem(Path::new("done_bg1_bgffffff.png")),
+            Some("done_bg1")
+        );
+        assert_eq!(background_cache_stem(Path::new("done_bgwhite.png")), None);
+        assert_eq!(
+            background_cache_stem(Path::new("done_bg1a2b3c4d.png")),
+            None
+        );
+        assert_eq!(background_cache_stem(Path::new("done_bg1a2b3c.wav")), None);
+        assert_eq!(background_cache_stem(Path::new("done_vol70.wav")), None);
     }
 
-    let persist: IPersistFile = link.cast()?;
-    let shortcut_wide: Vec<u16> = OsStr::new(&shortcut_path)
-        .encode_wide()
-        .chain(std::iter::once(0))
-        .collect();
-    unsafe {
-        persist
-            .Save(windows::core::PCWSTR(shortcut_wide.as_ptr()), true)
-            .context("Save shortcut failed")?;
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("toastmcp-test-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
     }
 
-    Ok(())
+    #[test]
+    fn forced_prepare_never_exposes_a_partial_wav() {
+        let dir = temp_dir("forced-prepare");
+        let stem = format!("toastmcp-test-{}-forced", std::process::id());
+        let source = dir.join(format!("{stem}.wav"));
+        let samples = vec![0x40; 256 * 1024];
+        std::fs::write(&source, wav_bytes(samples.len() as u32, &samples)).unwrap();
+        let cached = prepare_quiet_wav(&source, 0.5, true, false).unwrap();
+        assert!(cached.scaled);
+
+        let writer = {
+            let source = source.clone();
+            std::thread::spawn(move || {
+                for _ in 0..20 {
+                    prepare_quiet_wav(&source, 0.5, true, false).unwrap();
+                }
+            })
+        };
+        while !writer.is_finished() {
+            let data = std::fs::read(&cached.path).unwrap();
+            let info = wav::parse(&data).unwrap();
+            assert_eq!(data.len(), 44 + samples.len());
+            assert_eq!(info.data_size, samples.len());
+        }
+        writer.join().unwrap();
+
+        std::fs::remove_file(&cached.path).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 }
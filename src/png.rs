@@ -0,0 +1,163 @@
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+
+const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// `IHDR` details of a PNG file, plus whether it carries transparency.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct PngInfo {
+    pub width: u32,
+    pub height: u32,
+    pub bit_depth: u8,
+    pub color_type: u8,
+    /// A `tRNS` chunk adds transparency to palette, grey and RGB images.
+    pub has_trns: bool,
+}
+
+impl PngInfo {
+    /// Grey+alpha (4) and RGBA (6) have an alpha channel; other types need `tRNS`.
+    pub fn has_alpha(&self) -> bool {
+        matches!(self.color_type, 4 | 6) || self.has_trns
+    }
+}
+
+pub fn parse(data: &[u8]) -> Result<PngInfo> {
+    if !data.starts_with(SIGNATURE) {
+        return Err(anyhow!("not a PNG file"));
+    }
+
+    let mut cursor = SIGNATURE.len();
+    let mut header: Option<(u32, u32, u8, u8)> = None;
+    let mut has_trns = false;
+
+    while cursor + 8 <= data.len() {
+        let chunk_size = u32::from_be_bytes([
+            data[cursor],
+            data[cursor + 1],
+            data[cursor + 2],
+            data[cursor + 3],
+        ]) as usize;
+        let chunk_id = &data[cursor + 4..cursor + 8];
+        let chunk_start = cursor + 8;
+        // Chunk data is followed by a 4-byte CRC.
+        let chunk_end = chunk_start.saturating_add(chunk_size);
+        if chunk_end > data.len() {
+            break;
+        }
+
+        match chunk_id {
+            b"IHDR" if chunk_size >= 10 => {
+                let width = u32::from_be_bytes([
+                    data[chunk_start],
+                    data[chunk_start + 1],
+                    data[chunk_start + 2],
+                    data[chunk_start + 3],
+                ]);
+                let height = u32::from_be_bytes([
+                    data[chunk_start + 4],
+                    data[chunk_start + 5],
+                    data[chunk_start + 6],
+                    data[chunk_start + 7],
+                ]);
+                header = Some((width, height, data[chunk_start + 8], data[chunk_start + 9]));
+            }
+            b"tRNS" => has_trns = true,
+            b"IDAT" | b"IEND" => break,
+            _ => {}
+        }
+
+        cursor = chunk_end.saturating_add(4);
+    }
+
+    let (width, height, bit_depth, color_type) =
+        header.ok_or_else(|| anyhow!("missing IHDR chunk"))?;
+    Ok(PngInfo {
+        width,
+        height,
+        bit_depth,
+        color_type,
+        has_trns,
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    fn chunk(data: &mut Vec<u8>, id: &[u8; 4], body: &[u8]) {
+        data.extend_from_slice(&(body.len() as u32).to_be_bytes());
+        data.extend_from_slice(id);
+        data.extend_from_slice(body);
+        data.extend_from_slice(&[0; 4]);
+    }
+
+    /// A PNG with just an `IHDR` (8-bit, `color_type`), an optional `tRNS` and `IEND`.
+    pub(crate) fn png_bytes(width: u32, height: u32, color_type: u8, trns: bool) -> Vec<u8> {
+        let mut header = Vec::new();
+        header.extend_from_slice(&width.to_be_bytes());
+        header.extend_from_slice(&height.to_be_bytes());
+        header.extend_from_slice(&[8, color_type, 0, 0, 0]);
+        let mut data = SIGNATURE.to_vec();
+        chunk(&mut data, b"IHDR", &header);
+        if trns {
+            chunk(&mut data, b"tRNS", &[0, 0]);
+        }
+        chunk(&mut data, b"IEND", &[]);
+        data
+    }
+
+    #[test]
+    fn parse_reads_the_ihdr() {
+        let info = parse(&png_bytes(640, 480, 6, false)).unwrap();
+        assert_eq!((info.width, info.height), (640, 480));
+        assert_eq!((info.bit_depth, info.color_type), (8, 6));
+        assert!(!info.has_trns);
+        assert!(info.has_alpha());
+        assert!(parse(&png_bytes(16, 16, 4, false)).unwrap().has_alpha());
+        assert!(!parse(&png_bytes(16, 16, 2, false)).unwrap().has_alpha());
+    }
+
+    #[test]
+    fn trns_gives_other_color_types_alpha() {
+        for color_type in [0, 2, 3] {
+            let info = parse(&png_bytes(16, 16, color_type, true)).unwrap();
+            assert!(info.has_trns);
+            assert!(info.has_alpha(), "color type {color_type}");
+        }
+    }
+
+    #[test]
+    fn chunks_after_the_image_data_are_ignored() {
+        let mut data = png_bytes(16, 16, 2, false);
+        data.truncate(data.len() - 12);
+        chunk(&mut data, b"IDAT", &[0; 4]);
+        chunk(&mut data, b"tRNS", &[0, 0]);
+        assert!(!parse(&data).unwrap().has_trns);
+    }
+
+    #[test]
+    fn truncated_or_foreign_data_is_an_error() {
+        assert_eq!(parse(b"").unwrap_err().to_string(), "not a PNG file");
+        assert_eq!(parse(b"GIF89a").unwrap_err().to_string(), "not a PNG file");
+        assert_eq!(
+            parse(SIGNATURE).unwrap_err().to_string(),
+            "missing IHDR chunk"
+        );
+
+        let data = png_bytes(16, 16, 6, false);
+        // Cut inside the IHDR body, then inside its length and id.
+        for len in [SIGNATURE.len() + 12, SIGNATURE.len() + 5] {
+            let err = parse(&data[..len]).unwrap_err().to_string();
+            assert_eq!(err, "missing IHDR chunk", "at {len} bytes");
+        }
+        // A chunk claiming more data than there is stops the scan without panicking.
+        let mut data = SIGNATURE.to_vec();
+        data.extend_from_slice(&u32::MAX.to_be_bytes());
+        data.extend_from_slice(b"IHDR");
+        assert!(parse(&data).is_err());
+        // An IHDR too short to hold the header fields doesn't count.
+        let mut data = SIGNATURE.to_vec();
+        chunk(&mut data, b"IHDR", &[0; 9]);
+        assert!(parse(&data).is_err());
+    }
+}
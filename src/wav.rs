@@ -0,0 +1,242 @@
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+
+/// `fmt ` and `data` chunk details of a RIFF/WAVE file.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct WavInfo {
+    pub audio_format: u16,
+    pub channels: u16,
+    pub sample_rate: u32,
+    pub bits_per_sample: u16,
+    #[serde(skip)]
+    pub data_start: usize,
+    pub data_size: usize,
+}
+
+impl WavInfo {
+    /// Only 16-bit PCM can be volume-scaled; otherwise returns why not.
+    pub fn unscalable_reason(&self) -> Option<String> {
+        if self.audio_format != 1 {
+            return Some(format!(
+                "unsupported audio format {} (only PCM can be scaled)",
+                self.audio_format
+            ));
+        }
+        if self.bits_per_sample != 16 {
+            return Some(format!(
+                "unsupported bit depth {} (only 16-bit can be scaled)",
+                self.bits_per_sample
+            ));
+        }
+        None
+    }
+}
+
+/// Scales one 16-bit sample, rounding to nearest and clamping instead of wrapping.
+pub fn scale_i16(sample: i16, volume: f32) -> i16 {
+    (sample as f32 * volume)
+        .round()
+        .clamp(i16::MIN as f32, i16::MAX as f32) as i16
+}
+
+/// Scales the 16-bit little-endian samples in `info`'s data chunk in place.
+/// A trailing odd byte, or data past the end of the buffer, is left alone.
+pub fn scale_pcm16(data: &mut [u8], info: &WavInfo, volume: f32) {
+    let data_end = info
+        .data_start
+        .saturating_add(info.data_size)
+        .min(data.len());
+    let Some(samples) = data.get_mut(info.data_start..data_end) else {
+        return;
+    };
+    for sample in samples.chunks_exact_mut(2) {
+        let scaled = scale_i16(i16::from_le_bytes([sample[0], sample[1]]), volume);
+        sample.copy_from_slice(&scaled.to_le_bytes());
+    }
+}
+
+pub fn parse(data: &[u8]) -> Result<WavInfo> {
+    if data.len() < 12 || &data[0..4] != b"RIFF" || &data[8..12] != b"WAVE" {
+        return Err(anyhow!("not a RIFF/WAVE file"));
+    }
+
+    let mut cursor = 12;
+    let mut fmt_chunk: Option<(u16, u16, u32, u16)> = None;
+    let mut data_chunk: Option<(usize, usize)> = None;
+
+    while cursor + 8 <= data.len() {
+        let chunk_id = &data[cursor..cursor + 4];
+        let chunk_size = u32::from_le_bytes([
+            data[cursor + 4],
+            data[cursor + 5],
+            data[cursor + 6],
+            data[cursor + 7],
+        ]) as usize;
+        let chunk_start = cursor + 8;
+        let chunk_end = chunk_start.saturating_add(chunk_size);
+        if chunk_end > data.len() {
+            // Streaming writers often leave the data size unset or too large; play
+            // whatever samples are actually there.
+            if chunk_id == b"data" {
+                data_chunk = Some((chunk_start, data.len() - chunk_start));
+            }
+            break;
+        }
+
+        if chunk_id == b"fmt " && chunk_size >= 16 {
+            let audio_format = u16::from_le_bytes([data[chunk_start], data[chunk_start + 1]]);
+            let channels = u16::from_le_bytes([data[chunk_start + 2], data[chunk_start + 3]]);
+            let sample_rate = u32::from_le_bytes([
+                data[chunk_start + 4],
+                data[chunk_start + 5],
+                data[chunk_start + 6],
+                data[chunk_start + 7],
+            ]);
+            let bits_per_sample = u16::from_le_bytes([
+                data[chunk_start + 14],
+                data[chunk_start + 15],
+            ]);
+            fmt_chunk = Some((audio_format, channels, sample_rate, bits_per_sample));
+        } else if chunk_id == b"data" {
+            data_chunk = Some((chunk_start, chunk_size));
+        }
+
+        cursor = chunk_end + (chunk_size % 2);
+    }
+
+    let (audio_format, channels, sample_rate, bits_per_sample) =
+        fmt_chunk.ok_or_else(|| anyhow!("missing fmt chunk"))?;
+    let (data_start, data_size) = data_chunk.ok_or_else(|| anyhow!("missing data chunk"))?;
+    Ok(WavInfo {
+        audio_format,
+        channels,
+        sample_rate,
+        bits_per_sample,
+        data_start,
+        data_size,
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// A mono 16-bit PCM WAV whose data chunk header claims `declared_data_size` bytes.
+    pub(crate) fn wav_bytes(declared_data_size: u32, samples: &[u8]) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(b"RIFF");
+        data.extend_from_slice(&(36 + samples.len() as u32).to_le_bytes());
+        data.extend_from_slice(b"WAVE");
+        data.extend_from_slice(b"fmt ");
+        data.extend_from_slice(&16u32.to_le_bytes());
+        data.extend_from_slice(&1u16.to_le_bytes());
+        data.extend_from_slice(&1u16.to_le_bytes());
+        data.extend_from_slice(&44_100u32.to_le_bytes());
+        data.extend_from_slice(&88_200u32.to_le_bytes());
+        data.extend_from_slice(&2u16.to_le_bytes());
+        data.extend_from_slice(&16u16.to_le_bytes());
+        data.extend_from_slice(b"data");
+        data.extend_from_slice(&declared_data_size.to_le_bytes());
+        data.extend_from_slice(samples);
+        data
+    }
+
+    #[test]
+    fn scale_i16_clamps_at_the_limits() {
+        assert_eq!(scale_i16(i16::MAX, 2.0), i16::MAX);
+        assert_eq!(scale_i16(i16::MIN, 2.0), i16::MIN);
+        assert_eq!(scale_i16(i16::MIN, 1.0), i16::MIN);
+        assert_eq!(scale_i16(i16::MAX, 1.0), i16::MAX);
+    }
+
+    #[test]
+    fn scale_i16_at_zero_and_full_volume() {
+        assert_eq!(scale_i16(12_345, 0.0), 0);
+        assert_eq!(scale_i16(-12_345, 0.0), 0);
+        assert_eq!(scale_i16(12_345, 1.0), 12_345);
+        assert_eq!(scale_i16(-12_345, 1.0), -12_345);
+    }
+
+    #[test]
+    fn scale_i16_rounds_to_nearest() {
+        assert_eq!(scale_i16(3, 0.5), 2);
+        assert_eq!(scale_i16(-3, 0.5), -2);
+        assert_eq!(scale_i16(1, 0.4), 0);
+    }
+
+    #[test]
+    fn scale_pcm16_leaves_trailing_odd_byte() {
+        let mut data = wav_bytes(5, &[0x10, 0x00, 0x20, 0x00, 0x7f]);
+        let info = parse(&data).unwrap();
+        scale_pcm16(&mut data, &info, 0.5);
+        assert_eq!(&data[44..], &[0x08, 0x00, 0x10, 0x00, 0x7f]);
+    }
+
+    #[test]
+    fn scale_pcm16_stops_at_end_of_buffer() {
+        let mut data = wav_bytes(4, &[0x10, 0x00, 0x20, 0x00]);
+        let info = WavInfo {
+            data_size: 1_000,
+            ..parse(&data).unwrap()
+        };
+        scale_pcm16(&mut data, &info, 0.0);
+        assert_eq!(&data[44..], &[0, 0, 0, 0]);
+
+        let info = WavInfo {
+            data_start: data.len() + 10,
+            ..info
+        };
+        scale_pcm16(&mut data, &info, 0.0);
+    }
+
+    #[test]
+    fn scale_pcm16_skips_the_header() {
+        let mut data = wav_bytes(2, &[0x10, 0x00]);
+        let original = data.clone();
+        let info = parse(&data).unwrap();
+        scale_pcm16(&mut data, &info, 0.0);
+        assert_eq!(&data[..44], &original[..44]);
+        assert_eq!(&data[44..], &[0, 0]);
+    }
+
+    #[test]
+    fn parse_reads_fmt_and_data() {
+        let data = wav_bytes(4, &[1, 0, 2, 0]);
+        let info = parse(&data).unwrap();
+        assert_eq!(info.audio_format, 1);
+        assert_eq!(info.channels, 1);
+        assert_eq!(info.sample_rate, 44_100);
+        assert_eq!(info.bits_per_sample, 16);
+        assert_eq!(info.data_start, 44);
+        assert_eq!(info.data_size, 4);
+        assert!(info.unscalable_reason().is_none());
+    }
+
+    #[test]
+    fn parse_rejects_truncated_headers() {
+        let data = wav_bytes(4, &[1, 0, 2, 0]);
+        assert!(parse(&[]).is_err());
+        assert!(parse(&data[..11]).is_err());
+        // RIFF header only: no chunks at all.
+        assert!(parse(&data[..12]).is_err());
+        // Cut inside the fmt chunk.
+        assert!(parse(&data[..30]).is_err());
+        // fmt complete, data chunk header cut short.
+        assert!(parse(&data[..40]).is_err());
+    }
+
+    #[test]
+    fn parse_rejects_non_wave_data() {
+        let mut data = wav_bytes(4, &[1, 0, 2, 0]);
+        data[8..12].copy_from_slice(b"AVI ");
+        assert!(parse(&data).is_err());
+    }
+
+    #[test]
+    fn parse_clamps_data_chunk_larger_than_file() {
+        let data = wav_bytes(1_000, &[1, 0, 2, 0]);
+        let info = parse(&data).unwrap();
+        assert_eq!(info.data_start, 44);
+        assert_eq!(info.data_size, 4);
+    }
+}